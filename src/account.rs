@@ -2,8 +2,11 @@ use iced::{
     button, Align, Button, Column, Command, Element, HorizontalAlignment, Length, Row, Text,
 };
 
+use uuid::Uuid;
+
+use crate::config;
 use crate::data::Account;
-use crate::{error::Error, receive, send, transactions};
+use crate::{error::Error, receive, send, settings, tasks, transactions};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -17,6 +20,7 @@ pub enum Message {
     Send(send::Message),
     Receive(receive::Message),
     Transactions(transactions::Message),
+    Settings(settings::Message),
 
     // Account updates
     AccountUpdated(Result<Account, Error>),
@@ -27,7 +31,7 @@ pub enum MainView {
     Send(send::Page),
     Receive(receive::Page),
     Transactions(transactions::Page),
-    Settings,
+    Settings(settings::Page),
 }
 
 #[derive(Debug, Clone)]
@@ -72,7 +76,11 @@ impl<'a> Nav {
     }
     pub fn view(&mut self) -> Element<Message> {
         let balance = match self.account.balance {
-            Some(balance) => Text::new(format!("{}: {}", self.account.name, balance)),
+            Some(balance) => Text::new(format!(
+                "{}: {}",
+                self.account.name,
+                config::get().unit.format(balance as i64)
+            )),
             None => Text::new("..."),
         };
         let buttons = Column::new()
@@ -118,12 +126,19 @@ impl<'a> Page {
             nav: Nav::new(account),
         }
     }
+    pub fn id(&self) -> Uuid {
+        self.account.id
+    }
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match message.clone() {
             Message::SendView => {
                 // FIXME: clone
                 self.view = MainView::Send(send::Page::new(self.account.clone()));
-                Command::none()
+                Command::perform(
+                    tasks::estimate_fees(self.account.network),
+                    send::Message::FeesEstimated,
+                )
+                .map(Message::Send)
             }
             Message::ReceiveView => {
                 self.view = MainView::Receive(receive::Page::new(self.account.clone()));
@@ -134,12 +149,17 @@ impl<'a> Page {
                 Command::none()
             }
             Message::SettingsView => {
-                self.view = MainView::Settings;
+                self.view = MainView::Settings(settings::Page::new(self.account.clone()));
                 Command::none()
             }
             // FIXME: these are verbose ... maybe have self.view should just map .update to all
             // active child like I did for the Node enum in druid ... ???
             Message::Send(msg) => {
+                // Main has already saved the label to the registry
+                if let send::Message::SaveLabel(ref outpoint, ref label) = msg {
+                    self.account.set_label(outpoint, label);
+                    self.nav.account.set_label(outpoint, label);
+                }
                 if let MainView::Send(ref mut view) = &mut self.view {
                     view.update(msg).map(Message::Send)
                 } else {
@@ -160,6 +180,20 @@ impl<'a> Page {
                     Command::none()
                 }
             }
+            Message::Settings(msg) => {
+                // Main has already saved the edited account to the registry
+                if let settings::Message::SaveAccount(ref account)
+                | settings::Message::AccountEncrypted(ref account) = msg
+                {
+                    self.account = account.clone();
+                    self.nav.account = account.clone();
+                }
+                if let MainView::Settings(ref mut view) = &mut self.view {
+                    view.update(msg).map(Message::Settings)
+                } else {
+                    Command::none()
+                }
+            }
             Message::AccountUpdated(result) => {
                 // FIXME: record error state
                 if let Ok(account) = result {
@@ -177,7 +211,9 @@ impl<'a> Page {
                         MainView::Transactions(ref mut view) => {
                             view.update(transactions::Message::AccountUpdated(account));
                         }
-                        _ => {}
+                        MainView::Settings(ref mut view) => {
+                            view.update(settings::Message::AccountUpdated(account));
+                        }
                     }
                 }
                 self.nav.update(message);
@@ -192,7 +228,7 @@ impl<'a> Page {
             }
             MainView::Send(ref mut send) => send.view().map(Message::Send),
             MainView::Receive(ref mut receive) => receive.view().map(Message::Receive),
-            MainView::Settings => Text::new("Settings").into(),
+            MainView::Settings(ref mut settings) => settings.view().map(Message::Settings),
         };
 
         let content: Element<_> = Column::new()
@@ -0,0 +1,352 @@
+//! Printable backup sheet for a new account: everything needed to find its coins again, as an
+//! A4 SVG any browser can print
+
+use bitcoin::Network;
+use qrcode::{Color, QrCode};
+
+use crate::error::{consume_library_error, Error};
+use crate::utils::{key_fingerprints, public_descriptor, with_checksum};
+
+const WIDTH: f64 = 210.0;
+const HEIGHT: f64 = 297.0;
+const MARGIN: f64 = 15.0;
+const WORD_COLUMNS: usize = 3;
+/// Descriptors are too long for one line
+const DESCRIPTOR_LINE: usize = 64;
+const QR_SIZE: f64 = 70.0;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn text(x: f64, y: f64, size: f64, content: &str) -> String {
+    format!(
+        "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{}\">{}</text>\n",
+        x,
+        y,
+        size,
+        escape(content)
+    )
+}
+
+/// The dark modules of `data`'s QR code as one path of unit squares, after a 4 module quiet
+/// zone, and the code's width in modules including the quiet zone
+fn qr_path(data: &str) -> Result<(String, usize), Error> {
+    let code = QrCode::new(data).map_err(consume_library_error)?;
+    let width = code.width();
+    let mut path = String::new();
+    for (i, color) in code.to_colors().iter().enumerate() {
+        if *color == Color::Dark {
+            path.push_str(&format!("M{} {}h1v1h-1z", i % width + 4, i / width + 4));
+        }
+    }
+    Ok((path, width + 8))
+}
+
+/// The QR code scaled to `size` millimetres
+fn qr(data: &str, x: f64, y: f64, size: f64) -> Result<String, Error> {
+    let (path, modules) = qr_path(data)?;
+    Ok(format!(
+        "<g transform=\"translate({:.1} {:.1}) scale({:.4})\">\
+         <rect width=\"{}\" height=\"{}\" fill=\"white\"/><path d=\"{}\" fill=\"black\"/></g>\n",
+        x,
+        y,
+        size / modules as f64,
+        modules,
+        modules,
+        path
+    ))
+}
+
+fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "Mainnet",
+        Network::Testnet => "Testnet",
+        Network::Regtest => "Regtest",
+    }
+}
+
+/// Lowest baseline content may use, keeping clear of the footer
+const BOTTOM: f64 = HEIGHT - MARGIN - 8.0;
+const FINGERPRINTS_PER_LINE: usize = 5;
+
+/// Content laid out top to bottom, starting a new page whenever the next block would run into
+/// the footer
+struct Pages {
+    pages: Vec<String>,
+    /// Where the next block starts on the last page
+    y: f64,
+}
+
+impl Pages {
+    fn new() -> Self {
+        Self {
+            pages: vec![String::new()],
+            y: MARGIN,
+        }
+    }
+    /// Make room for a block `height` millimetres tall, on a new page if this one is full.
+    /// Returns where the block starts.
+    fn reserve(&mut self, height: f64) -> f64 {
+        if self.y + height > BOTTOM && self.y > MARGIN {
+            self.pages.push(String::new());
+            self.y = MARGIN;
+        }
+        let top = self.y;
+        self.y += height;
+        top
+    }
+    /// Skip `gap` millimetres, unless at the top of a page
+    fn space(&mut self, gap: f64) {
+        if self.y > MARGIN {
+            self.y += gap;
+        }
+    }
+    fn push(&mut self, svg: &str) {
+        self.pages
+            .last_mut()
+            .expect("there is always a page")
+            .push_str(svg);
+    }
+    /// A line of text of `size` taking `height` millimetres
+    fn line(&mut self, height: f64, size: f64, content: &str) {
+        let y = self.reserve(height) + height;
+        self.push(&text(MARGIN, y, size, content));
+    }
+}
+
+/// SVG backup sheet with the numbered seed words, the master fingerprint (one per cosigner for
+/// multisig), the network and the public descriptors with checksums, plus a QR of the receive
+/// descriptor. Multisig sheets pass no words, since each signer's seed is backed up on its own.
+/// Any private keys in the descriptors are replaced with public ones. Long multisig descriptors
+/// continue on further A4 pages, stacked in the one SVG so printing at 100% gives a page each.
+pub fn sheet(
+    name: &str,
+    network: Network,
+    words: &[String],
+    has_passphrase: bool,
+    descriptor: &str,
+    change_descriptor: Option<&str>,
+) -> Result<String, Error> {
+    let descriptor = with_checksum(&public_descriptor(descriptor))?;
+    let fingerprints: Vec<String> = key_fingerprints(&descriptor)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    if fingerprints.is_empty() {
+        return Err(Error::Import("Descriptor has no key".to_string()));
+    }
+
+    let mut pages = Pages::new();
+    pages.line(10.0, 9.0, &format!("{} backup", name));
+    pages.line(10.0, 4.5, &format!("Network: {}", network_name(network)));
+    if let [fingerprint] = fingerprints.as_slice() {
+        pages.line(7.0, 4.5, &format!("Master fingerprint: {}", fingerprint));
+    } else {
+        for (i, line) in fingerprints.chunks(FINGERPRINTS_PER_LINE).enumerate() {
+            let label = if i == 0 {
+                "Cosigner fingerprints: "
+            } else {
+                ""
+            };
+            pages.line(7.0, 4.5, &format!("{}{}", label, line.join(", ")));
+        }
+    }
+
+    if !words.is_empty() {
+        // Numbered down each column, like most wallets show them
+        pages.space(7.0);
+        let rows = (words.len() + WORD_COLUMNS - 1) / WORD_COLUMNS;
+        let top = pages.reserve(7.0 + 9.0 + rows as f64 * 8.0) + 7.0;
+        pages.push(&text(MARGIN, top, 6.0, "Seed words"));
+        let column_width = (WIDTH - 2.0 * MARGIN) / WORD_COLUMNS as f64;
+        for (i, word) in words.iter().enumerate() {
+            pages.push(&text(
+                MARGIN + (i / rows) as f64 * column_width,
+                top + 9.0 + (i % rows) as f64 * 8.0,
+                5.0,
+                &format!("{}. {}", i + 1, word),
+            ));
+        }
+        if has_passphrase {
+            pages.line(
+                6.0,
+                4.0,
+                "These words need a passphrase too, which is not on this sheet.",
+            );
+        }
+    }
+
+    pages.space(2.0);
+    pages.line(6.0, 6.0, "Descriptors");
+    let mut descriptors = vec![descriptor.clone()];
+    if let Some(change_descriptor) = change_descriptor {
+        descriptors.push(with_checksum(&public_descriptor(change_descriptor))?);
+    }
+    for descriptor in &descriptors {
+        pages.space(3.0);
+        for chunk in descriptor.as_bytes().chunks(DESCRIPTOR_LINE) {
+            let y = pages.reserve(5.0) + 5.0;
+            pages.push(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"3.5\" font-family=\"monospace\">{}</text>\n",
+                MARGIN,
+                y,
+                // Descriptors are ASCII, so byte chunks are whole characters
+                escape(&String::from_utf8_lossy(chunk))
+            ));
+        }
+    }
+
+    pages.space(5.0);
+    let top = pages.reserve(QR_SIZE);
+    pages.push(&qr(&descriptor, MARGIN, top, QR_SIZE)?);
+    pages.push(&text(
+        MARGIN + QR_SIZE + 5.0,
+        top + QR_SIZE / 2.0,
+        4.0,
+        "Receive descriptor, for watch-only wallets",
+    ));
+
+    let warning = if words.is_empty() {
+        "Anyone with this sheet can see the coins but not spend them. You also need enough of the \
+         signers' own backups to spend."
+    } else {
+        "Anyone with these words can spend the coins. Keep this sheet safe and never photograph it."
+    };
+    let count = pages.pages.len();
+    let mut body = String::new();
+    for (i, page) in pages.pages.iter().enumerate() {
+        body.push_str(&format!(
+            "<g transform=\"translate(0 {:.1})\">\n{}",
+            i as f64 * HEIGHT,
+            page
+        ));
+        body.push_str(&text(MARGIN, HEIGHT - MARGIN, 4.0, warning));
+        if count > 1 {
+            body.push_str(&text(
+                WIDTH - MARGIN - 25.0,
+                HEIGHT - MARGIN + 6.0,
+                4.0,
+                &format!("Page {} of {}", i + 1, count),
+            ));
+        }
+        body.push_str("</g>\n");
+    }
+
+    Ok(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" \
+         viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\">\n\
+         <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n{body}</svg>\n",
+        w = WIDTH,
+        h = HEIGHT * count as f64,
+        body = body
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::{ChildNumber, ExtendedPrivKey, ExtendedPubKey};
+
+    fn master(seed: u8) -> ExtendedPrivKey {
+        ExtendedPrivKey::new_master(Network::Testnet, &[seed; 32]).unwrap()
+    }
+
+    #[test]
+    fn qr_holds_public_descriptor_with_checksum() {
+        let secp = Secp256k1::new();
+        let master = master(1);
+        let private = format!("wpkh([{}]{}/0/*)", master.fingerprint(&secp), master);
+        let public = format!(
+            "wpkh([{}]{}/0/*)",
+            master.fingerprint(&secp),
+            ExtendedPubKey::from_private(&secp, &master)
+        );
+        let words = vec!["abandon".to_string(); 12];
+        let sheet = sheet("Savings", Network::Testnet, &words, false, &private, None).unwrap();
+
+        assert!(!sheet.contains("prv"));
+        let (with_checksum_path, _) = qr_path(&with_checksum(&public).unwrap()).unwrap();
+        let (without_checksum_path, _) = qr_path(&public).unwrap();
+        assert!(sheet.contains(&with_checksum_path));
+        assert!(!sheet.contains(&without_checksum_path));
+        assert!(sheet.contains(&format!(
+            "Master fingerprint: {}",
+            master.fingerprint(&secp)
+        )));
+        assert!(sheet.contains("12. abandon"));
+    }
+
+    #[test]
+    fn multisig_sheet_lists_cosigners() {
+        let secp = Secp256k1::new();
+        let keys: Vec<(String, String)> = [2, 3]
+            .iter()
+            .map(|seed| {
+                let master = master(*seed);
+                let account = master
+                    .derive_priv(&secp, &[ChildNumber::from_hardened_idx(48).unwrap()])
+                    .unwrap();
+                let fingerprint = master.fingerprint(&secp).to_string();
+                let key = format!("[{}/48']{}/0/*", fingerprint, account);
+                (fingerprint, key)
+            })
+            .collect();
+        let descriptor = format!("wsh(sortedmulti(2,{},{}))", keys[0].1, keys[1].1);
+        let sheet = sheet("Vault", Network::Testnet, &[], false, &descriptor, None).unwrap();
+
+        assert!(!sheet.contains("prv"));
+        assert!(!sheet.contains("Seed words"));
+        assert!(sheet.contains(&format!(
+            "Cosigner fingerprints: {}, {}",
+            keys[0].0, keys[1].0
+        )));
+    }
+
+    #[test]
+    fn long_multisig_sheet_continues_on_new_pages() {
+        let secp = Secp256k1::new();
+        let keys: Vec<String> = (1..=15)
+            .map(|seed| {
+                let master = master(seed);
+                let account = master
+                    .derive_priv(&secp, &[ChildNumber::from_hardened_idx(48).unwrap()])
+                    .unwrap();
+                format!(
+                    "[{}/48']{}/0/*",
+                    master.fingerprint(&secp),
+                    ExtendedPubKey::from_private(&secp, &account)
+                )
+            })
+            .collect();
+        let descriptor = format!("wsh(sortedmulti(8,{}))", keys.join(","));
+        let change = descriptor.replace("/0/*", "/1/*");
+        let sheet = sheet(
+            "Vault",
+            Network::Testnet,
+            &[],
+            false,
+            &descriptor,
+            Some(&change),
+        )
+        .unwrap();
+
+        assert!(sheet.contains(&format!("height=\"{}mm\"", HEIGHT * 2.0)));
+        assert!(sheet.contains("Page 2 of 2"));
+        // Nothing but the footer below the content area of any page
+        for (start, _) in sheet.match_indices(" y=\"") {
+            let rest = &sheet[start + 4..];
+            let y: f64 = rest[..rest.find('"').unwrap()].parse().unwrap();
+            assert!(y <= BOTTOM || y >= HEIGHT - MARGIN, "text at {}", y);
+        }
+        let (qr_start, _) = sheet.match_indices(") scale(").next().unwrap();
+        let translate = &sheet[sheet[..qr_start].rfind("translate(").unwrap() + 10..qr_start];
+        let top: f64 = translate.split(' ').nth(1).unwrap().parse().unwrap();
+        assert!(top + QR_SIZE <= BOTTOM);
+    }
+}
@@ -0,0 +1,245 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use bitcoin::Network;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Theme, Unit};
+use crate::error::Error;
+use crate::wallets::WALLETS;
+
+/// Environment variable overriding the data directory
+pub const DATADIR_ENV: &str = "JUNCTION_DATADIR";
+
+/// Command line flag overriding the data directory (and the environment variable)
+pub const DATADIR_FLAG: &str = "--datadir";
+
+static CONFIG: OnceCell<RwLock<Config>> = OnceCell::new();
+
+/// Electrum server for one network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Server {
+    /// `tcp://host:port` or `ssl://host:port`
+    pub url: String,
+    /// Hex SHA256 of the server's DER certificate. When set, the certificate is checked against
+    /// this instead of the system roots, which lets us use self-signed servers.
+    #[serde(default)]
+    pub certificate_fingerprint: Option<String>,
+    /// SOCKS5 proxy as `host:port`, e.g. `127.0.0.1:9050` for Tor
+    #[serde(default)]
+    pub proxy: Option<String>,
+}
+
+impl Server {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            certificate_fingerprint: None,
+            proxy: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Servers {
+    pub regtest: Server,
+    pub testnet: Server,
+    pub bitcoin: Server,
+}
+
+impl Default for Servers {
+    fn default() -> Self {
+        Self {
+            regtest: Server::new("tcp://localhost:51401"),
+            testnet: Server::new("ssl://electrum.blockstream.info:60002"),
+            bitcoin: Server::new("ssl://electrum.blockstream.info:50002"),
+        }
+    }
+}
+
+impl Servers {
+    pub fn get(&self, network: Network) -> &Server {
+        match network {
+            Network::Regtest => &self.regtest,
+            Network::Testnet => &self.testnet,
+            Network::Bitcoin => &self.bitcoin,
+        }
+    }
+    pub fn get_mut(&mut self, network: Network) -> &mut Server {
+        match network {
+            Network::Regtest => &mut self.regtest,
+            Network::Testnet => &mut self.testnet,
+            Network::Bitcoin => &mut self.bitcoin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    // Chosen at startup, so never persisted
+    #[serde(skip)]
+    pub datadir: PathBuf,
+    #[serde(default)]
+    pub servers: Servers,
+    #[serde(default)]
+    pub unit: Unit,
+    #[serde(default)]
+    pub theme: Theme,
+}
+
+impl Config {
+    /// Resolve the data directory from `--datadir`, then `$JUNCTION_DATADIR`, then the XDG data
+    /// directory (`~/.local/share/junction` on Linux), and load the settings saved there
+    pub fn load() -> Result<Self, Error> {
+        let datadir = resolve_datadir(env::args().skip(1), env::var_os(DATADIR_ENV));
+
+        let path = datadir.join("config.json");
+        let config = if path.exists() {
+            let saved: Config = serde_json::from_str(&fs::read_to_string(path)?)?;
+            Config { datadir, ..saved }
+        } else {
+            let config = Config {
+                datadir,
+                servers: Servers::default(),
+                unit: Unit::default(),
+                theme: Theme::default(),
+            };
+            // Write the defaults out so there's a file to edit
+            config.save()?;
+            config
+        };
+        Ok(config)
+    }
+    pub fn save(&self) -> Result<(), Error> {
+        fs::create_dir_all(&self.datadir)?;
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(self.config_path(), contents)?;
+        Ok(())
+    }
+    pub fn config_path(&self) -> PathBuf {
+        self.datadir.join("config.json")
+    }
+    /// Registry of every account, regardless of network
+    pub fn accounts_path(&self) -> PathBuf {
+        self.datadir.join("accounts.json")
+    }
+    /// Each network gets its own sled database so wallet trees for different chains never mix
+    pub fn network_dir(&self, network: Network) -> PathBuf {
+        self.datadir.join(network.to_string())
+    }
+}
+
+fn default_datadir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("junction")
+}
+
+/// Takes the environment variable's value rather than reading it, so tests don't race on the
+/// process environment
+fn resolve_datadir(args: impl Iterator<Item = String>, env: Option<OsString>) -> PathBuf {
+    datadir_from_args(args)
+        .or_else(|| env.map(PathBuf::from))
+        .unwrap_or_else(default_datadir)
+}
+
+fn datadir_from_args(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == DATADIR_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix(&format!("{}=", DATADIR_FLAG)) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Must be called once, before the application starts
+pub fn init(config: Config) {
+    CONFIG
+        .set(RwLock::new(config))
+        .expect("config already initialized");
+}
+
+pub fn get() -> Config {
+    CONFIG
+        .get()
+        .expect("config not initialized")
+        .read()
+        .unwrap()
+        .clone()
+}
+
+/// Persist `config` and make it the current one
+pub fn set(config: Config) -> Result<(), Error> {
+    config.save()?;
+    let servers_changed = get().servers != config.servers;
+    *CONFIG
+        .get()
+        .expect("config not initialized")
+        .write()
+        .unwrap() = config;
+    if servers_changed {
+        // Open wallets are still connected to the old servers
+        WALLETS.disconnect_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn datadir_flag() {
+        assert_eq!(
+            resolve_datadir(args(&["--datadir", "/tmp/flag"]), None),
+            PathBuf::from("/tmp/flag")
+        );
+        assert_eq!(
+            resolve_datadir(args(&["--datadir=/tmp/flag"]), None),
+            PathBuf::from("/tmp/flag")
+        );
+    }
+
+    #[test]
+    fn datadir_env() {
+        assert_eq!(
+            resolve_datadir(args(&[]), Some(OsString::from("/tmp/env"))),
+            PathBuf::from("/tmp/env")
+        );
+    }
+
+    #[test]
+    fn datadir_flag_beats_env() {
+        assert_eq!(
+            resolve_datadir(
+                args(&["--datadir", "/tmp/flag"]),
+                Some(OsString::from("/tmp/env"))
+            ),
+            PathBuf::from("/tmp/flag")
+        );
+    }
+
+    #[test]
+    fn datadir_default() {
+        assert_eq!(resolve_datadir(args(&[]), None), default_datadir());
+        // A flag missing its value falls through too
+        assert_eq!(
+            resolve_datadir(args(&["--datadir"]), None),
+            default_datadir()
+        );
+    }
+}
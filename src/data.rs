@@ -1,32 +1,192 @@
-use bitcoin::{util::address::Address, Network};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::amount::{Denomination, SignedAmount};
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{util::address::Address, Network, OutPoint, Txid};
 use iced::button;
-use magical_bitcoin_wallet::blockchain::ElectrumBlockchain;
 use magical_bitcoin_wallet::sled;
-use magical_bitcoin_wallet::types::TransactionDetails;
-use magical_bitcoin_wallet::Client as ElectrumClient;
-use magical_bitcoin_wallet::Wallet;
+use magical_bitcoin_wallet::types::{TransactionDetails, UTXO};
+use magical_bitcoin_wallet::OfflineWallet;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+use crate::encryption::{self, Encrypted};
 use crate::error::{consume_library_error, Error};
+use crate::utils::{
+    change_descriptor, fingerprints, key_origin, parse_key_origin, public_descriptor,
+};
+use crate::wallets::{ElectrumWallet, WALLETS};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
-    // TODO: uuid, receive/change descriptors, wallet<...>?
+    /// Stable identity used for tabs and sled tree names, so renaming never touches wallet state
+    pub id: Uuid,
     pub name: String,
+    /// Descriptor used for syncing and receiving. Only holds public keys unless this is an
+    /// account created before descriptors were encrypted.
     pub descriptor: String,
-    pub tab_button: button::State,
+    /// Descriptor for change outputs (the `/1/*` chain), public like `descriptor`. Only missing
+    /// for imported descriptors we couldn't derive a change chain from, which send change back to
+    /// receive addresses.
+    #[serde(default)]
+    pub change_descriptor: Option<String>,
+    /// Private descriptors, only ever decrypted in memory to sign
+    #[serde(default)]
+    pub encrypted_descriptor: Option<Encrypted>,
     pub network: Network,
+    /// How many addresses of each chain sync derives and watches. Not a stop gap: coins past
+    /// this many addresses are only found once earlier ones get used.
+    #[serde(default = "default_watched_addresses", alias = "gap_limit")]
+    pub watched_addresses: u32,
+    /// Unix timestamp (seconds) of when the account was created
+    pub created_at: u64,
+    /// Notes on coins, keyed by outpoint (`txid:vout`)
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+
+    // Runtime state, never persisted
+    #[serde(skip)]
+    pub tab_button: button::State,
+    #[serde(skip)]
     pub balance: Option<u64>,
+    #[serde(skip)]
     pub transactions: Option<Vec<TransactionDetails>>,
 }
 
+fn default_watched_addresses() -> u32 {
+    20
+}
+
+/// What gets encrypted: the private halves of both descriptors
+#[derive(Serialize, Deserialize)]
+struct PrivateDescriptors {
+    descriptor: String,
+    #[serde(default)]
+    change_descriptor: Option<String>,
+}
+
+/// One key while setting up an account. Multisig accounts combine several, and the fingerprint
+/// identifies keys shared with other accounts.
+#[derive(Debug, Clone)]
+pub struct Signer {
+    /// Fingerprint of the master key, shared by every key derived from the same seed
+    pub fingerprint: Fingerprint,
+    /// Where the key sits below the master key, if known
+    pub path: Option<DerivationPath>,
+    pub xpub: ExtendedPubKey,
+    /// Only known for keys created or restored here
+    pub xprv: Option<ExtendedPrivKey>,
+    /// Where the key came from, e.g. "New seed" or the account it was taken from
+    pub label: String,
+}
+
+impl Signer {
+    /// The key at `path` below `master`
+    pub fn from_master(
+        master: &ExtendedPrivKey,
+        path: DerivationPath,
+        label: String,
+    ) -> Result<Self, Error> {
+        let secp = Secp256k1::new();
+        let xprv = master
+            .derive_priv(&secp, &path)
+            .map_err(consume_library_error)?;
+        Ok(Self {
+            fingerprint: master.fingerprint(&secp),
+            path: Some(path),
+            xpub: ExtendedPubKey::from_private(&secp, &xprv),
+            xprv: Some(xprv),
+            label,
+        })
+    }
+    /// An imported key, under its master fingerprint and path when the origin was given
+    pub fn from_xpub(
+        xpub: ExtendedPubKey,
+        origin: Option<(Fingerprint, DerivationPath)>,
+        label: String,
+    ) -> Self {
+        let (fingerprint, path) = match origin {
+            Some((fingerprint, path)) => (fingerprint, Some(path)),
+            None => (xpub.fingerprint(), None),
+        };
+        Self {
+            fingerprint,
+            path,
+            xpub,
+            xprv: None,
+            label,
+        }
+    }
+    /// The key of a single-key account's `descriptor` with its origin, private if it has one.
+    /// Multisig descriptors are refused, since nothing says which of their keys is ours.
+    pub fn from_descriptor(descriptor: &str, label: String) -> Result<Self, Error> {
+        if descriptor.contains("multi(") {
+            return Err(Error::Import(
+                "Multisig accounts can't be used as a signer".to_string(),
+            ));
+        }
+        let secp = Secp256k1::new();
+        for token in descriptor.split(|c: char| "()[],/*'#".contains(c)) {
+            let (xpub, xprv) = if let Ok(xprv) = ExtendedPrivKey::from_str(token) {
+                (ExtendedPubKey::from_private(&secp, &xprv), Some(xprv))
+            } else if let Ok(xpub) = ExtendedPubKey::from_str(token) {
+                (xpub, None)
+            } else {
+                continue;
+            };
+            let (fingerprint, path) = match parse_key_origin(descriptor) {
+                Some((fingerprint, path)) => (fingerprint, Some(path)),
+                None => (xpub.fingerprint(), None),
+            };
+            return Ok(Self {
+                fingerprint,
+                path,
+                xpub,
+                xprv,
+                label,
+            });
+        }
+        Err(Error::Import("Account has no extended key".to_string()))
+    }
+    /// The key as it appears in a descriptor, with its origin: the xprv if we have it
+    pub fn key(&self) -> String {
+        let key = match &self.xprv {
+            Some(xprv) => xprv.to_string(),
+            None => self.xpub.to_string(),
+        };
+        match &self.path {
+            Some(path) => format!("{}{}", key_origin(self.fingerprint, path), key),
+            None => key,
+        }
+    }
+}
+
 impl Account {
-    pub fn new(name: String, descriptor: String) -> Self {
-        let network = Network::Regtest;
+    pub fn new(
+        name: String,
+        network: Network,
+        descriptor: String,
+        change_descriptor: Option<String>,
+    ) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
         Self {
+            id: Uuid::new_v4(),
             name,
             descriptor,
-            tab_button: button::State::new(),
+            change_descriptor,
+            encrypted_descriptor: None,
             network,
+            watched_addresses: default_watched_addresses(),
+            created_at,
+            labels: BTreeMap::new(),
+            tab_button: button::State::new(),
             balance: None,
             transactions: None,
         }
@@ -34,48 +194,210 @@ impl Account {
 }
 
 impl Account {
-    pub fn get_wallet(&self) -> Result<Wallet<ElectrumBlockchain, sled::Tree>, Error> {
-        let host = "tcp://localhost:51401";
-        let proxy = None;
-
-        let client = ElectrumClient::new(&host, proxy)?;
-        let blockchain = ElectrumBlockchain::from(client);
-
-        let db = sled::open("/home/justin/.iced")?;
-
-        // TODO: uuid
-        let tree = db.open_tree(self.name.clone())?; // TODO: handle this
+    /// Slow: runs the KDF. Keeps only the public halves of the descriptors in the clear.
+    pub fn encrypt(
+        &mut self,
+        private_descriptor: &str,
+        private_change_descriptor: Option<&str>,
+        password: &str,
+    ) {
+        self.descriptor = public_descriptor(private_descriptor);
+        self.change_descriptor = private_change_descriptor.map(public_descriptor);
+        let payload = PrivateDescriptors {
+            descriptor: private_descriptor.to_string(),
+            change_descriptor: private_change_descriptor.map(String::from),
+        };
+        let plaintext = serde_json::to_vec(&payload).expect("descriptors serialize");
+        self.encrypted_descriptor = Some(encryption::encrypt(&plaintext, password));
+    }
+    /// Private receive and change descriptors. Slow if encrypted.
+    pub fn private_descriptors(&self, password: &str) -> Result<(String, Option<String>), Error> {
+        match &self.encrypted_descriptor {
+            Some(encrypted) => {
+                let plaintext = encryption::decrypt(encrypted, password)?;
+                // Early encrypted accounts stored the bare receive descriptor
+                let payload = match serde_json::from_slice::<PrivateDescriptors>(&plaintext) {
+                    Ok(payload) => payload,
+                    Err(_) => PrivateDescriptors {
+                        descriptor: String::from_utf8(plaintext).map_err(consume_library_error)?,
+                        change_descriptor: None,
+                    },
+                };
+                // Their public change descriptor was added by migration, so derive the private
+                // one the same way
+                let change = match payload.change_descriptor {
+                    Some(change) => Some(change),
+                    None if self.change_descriptor.is_some() => {
+                        change_descriptor(&payload.descriptor)
+                    }
+                    None => None,
+                };
+                Ok((payload.descriptor, change))
+            }
+            // Accounts from before encryption keep their private descriptor in the clear
+            None => Ok((self.descriptor.clone(), self.change_descriptor.clone())),
+        }
+    }
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted_descriptor.is_some()
+    }
+    pub fn is_multisig(&self) -> bool {
+        self.descriptor.contains("multi(")
+    }
+    /// No private keys anywhere, so we can track funds but never sign
+    pub fn is_watch_only(&self) -> bool {
+        !self.is_encrypted() && public_descriptor(&self.descriptor) == self.descriptor
+    }
+}
 
-        println!("{}", &self.descriptor);
-        Wallet::new(
-            &self.descriptor,
-            None,
-            // HELP: self network or node network?
-            self.network.clone(),
-            tree,
-            blockchain,
-        )
-        .map_err(consume_library_error)
+impl Account {
+    pub fn tree_name(&self) -> String {
+        self.id.to_string()
+    }
+    pub fn label(&self, outpoint: &OutPoint) -> Option<&String> {
+        self.labels.get(&outpoint.to_string())
+    }
+    /// Label a coin, or clear its label if `label` is blank
+    pub fn set_label(&mut self, outpoint: &OutPoint, label: &str) {
+        if label.trim().is_empty() {
+            self.labels.remove(&outpoint.to_string());
+        } else {
+            self.labels
+                .insert(outpoint.to_string(), label.trim().to_string());
+        }
+    }
+    /// Wallet holding the private keys. The decrypted descriptors are dropped along with it.
+    pub fn get_signing_wallet(&self, password: &str) -> Result<OfflineWallet<sled::Tree>, Error> {
+        let (descriptor, change_descriptor) = self.private_descriptors(password)?;
+        WALLETS.signing_wallet(self, &descriptor, change_descriptor.as_deref())
+    }
+    /// Whether `signer`'s key is used by this account
+    pub fn has_signer(&self, signer: &Signer) -> bool {
+        fingerprints(&self.descriptor).contains(&signer.fingerprint)
+    }
+    pub fn with_wallet<T>(
+        &self,
+        f: impl FnOnce(&ElectrumWallet) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        WALLETS.with_wallet(self, f)
     }
     pub fn sync(&self, max_address_param: Option<u32>) -> Result<(), Error> {
-        let wallet = self.get_wallet()?;
-        wallet
-            .sync(max_address_param)
-            .map_err(consume_library_error)
+        self.with_wallet(|wallet| wallet.sync(max_address_param).map_err(Error::from))
     }
     pub fn address(&self) -> Result<Address, Error> {
-        let wallet = self.get_wallet()?;
-        wallet.get_new_address().map_err(consume_library_error)
+        self.with_wallet(|wallet| wallet.get_new_address().map_err(Error::from))
     }
     pub fn get_balance(&self) -> Result<u64, Error> {
-        let wallet = self.get_wallet()?;
-        wallet.get_balance().map_err(consume_library_error)
+        self.with_wallet(|wallet| wallet.get_balance().map_err(Error::from))
+    }
+    pub fn list_unspent(&self) -> Result<Vec<UTXO>, Error> {
+        self.with_wallet(|wallet| wallet.list_unspent().map_err(Error::from))
     }
     pub fn list_transactions(&self) -> Result<Vec<TransactionDetails>, Error> {
-        let wallet = self.get_wallet()?;
-        wallet
-            .list_transactions(false)
-            .map_err(consume_library_error)
+        self.with_wallet(|wallet| wallet.list_transactions(false).map_err(Error::from))
+    }
+}
+
+/// An unspent output of an account, for coin control
+#[derive(Debug, Clone)]
+pub struct Coin {
+    pub outpoint: OutPoint,
+    pub amount: u64,
+    /// Zero while unconfirmed
+    pub confirmations: u32,
+    /// None for scripts without an address form
+    pub address: Option<Address>,
+}
+
+/// What signing a reviewed transaction came to
+#[derive(Debug, Clone)]
+pub enum Signed {
+    Broadcast(Txid),
+    /// Signed as far as this account's keys go, for the other signers of a multisig to finish
+    Partially(PartiallySignedTransaction),
+}
+
+/// How the setup wizard gets the account's keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Generate,
+    Restore,
+    /// Restore from SLIP-39 shares made by this wallet
+    Shamir,
+    WatchOnly,
+    Multisig,
+}
+
+impl Method {
+    pub fn all() -> [Method; 5] {
+        [
+            Method::Generate,
+            Method::Restore,
+            Method::Shamir,
+            Method::WatchOnly,
+            Method::Multisig,
+        ]
+    }
+}
+
+impl From<Method> for String {
+    fn from(method: Method) -> String {
+        String::from(match method {
+            Method::Generate => "Generate new words",
+            Method::Restore => "Restore from words",
+            Method::Shamir => "Restore from Shamir shares (SLIP-39)",
+            Method::WatchOnly => "Watch-only from xpub or descriptor",
+            Method::Multisig => "Multisig from several keys",
+        })
+    }
+}
+
+/// Output script for single-key accounts, which also picks the BIP44/49/84 derivation purpose
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    NativeSegwit,
+    NestedSegwit,
+    Legacy,
+}
+
+impl ScriptType {
+    pub fn all() -> [ScriptType; 3] {
+        [
+            ScriptType::NativeSegwit,
+            ScriptType::NestedSegwit,
+            ScriptType::Legacy,
+        ]
+    }
+    pub fn purpose(&self) -> u32 {
+        match self {
+            Self::Legacy => 44,
+            Self::NestedSegwit => 49,
+            Self::NativeSegwit => 84,
+        }
+    }
+    /// Descriptor paying to `key`
+    pub fn descriptor(&self, key: &str) -> String {
+        match self {
+            Self::Legacy => format!("pkh({})", key),
+            Self::NestedSegwit => format!("sh(wpkh({}))", key),
+            Self::NativeSegwit => format!("wpkh({})", key),
+        }
+    }
+}
+
+impl Default for ScriptType {
+    fn default() -> Self {
+        ScriptType::NativeSegwit
+    }
+}
+
+impl From<ScriptType> for String {
+    fn from(script_type: ScriptType) -> String {
+        String::from(match script_type {
+            ScriptType::NativeSegwit => "Native segwit (bc1...)",
+            ScriptType::NestedSegwit => "Nested segwit (3...)",
+            ScriptType::Legacy => "Legacy (1...)",
+        })
     }
 }
 
@@ -97,6 +419,107 @@ impl Entropy {
             Self::High => 24,
         }
     }
+    pub fn bits(&self) -> u32 {
+        match self {
+            Self::Low => 128,
+            Self::Medium => 192,
+            Self::High => 256,
+        }
+    }
+}
+
+/// BIP39 word list language
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Japanese,
+    Korean,
+    Spanish,
+    ChineseSimplified,
+    ChineseTraditional,
+    French,
+    Italian,
+    Czech,
+}
+
+impl Language {
+    pub fn all() -> [Language; 9] {
+        [
+            Language::English,
+            Language::Japanese,
+            Language::Korean,
+            Language::Spanish,
+            Language::ChineseSimplified,
+            Language::ChineseTraditional,
+            Language::French,
+            Language::Italian,
+            Language::Czech,
+        ]
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::English
+    }
+}
+
+impl From<Language> for String {
+    fn from(language: Language) -> String {
+        String::from(match language {
+            Language::English => "English",
+            Language::Japanese => "日本語 (Japanese)",
+            Language::Korean => "한국어 (Korean)",
+            Language::Spanish => "Español (Spanish)",
+            Language::ChineseSimplified => "简体中文 (Chinese, simplified)",
+            Language::ChineseTraditional => "繁體中文 (Chinese, traditional)",
+            Language::French => "Français (French)",
+            Language::Italian => "Italiano (Italian)",
+            Language::Czech => "Čeština (Czech)",
+        })
+    }
+}
+
+/// Where a new seed's randomness comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropySource {
+    System,
+    Dice,
+    Coins,
+}
+
+impl EntropySource {
+    pub fn all() -> [EntropySource; 3] {
+        [
+            EntropySource::System,
+            EntropySource::Dice,
+            EntropySource::Coins,
+        ]
+    }
+    /// Bits of entropy in one fair roll or flip
+    pub fn bits_per_roll(&self) -> f64 {
+        match self {
+            Self::System => 0.0,
+            Self::Dice => 6f64.log2(),
+            Self::Coins => 1.0,
+        }
+    }
+}
+
+impl Default for EntropySource {
+    fn default() -> Self {
+        EntropySource::System
+    }
+}
+
+impl From<EntropySource> for String {
+    fn from(source: EntropySource) -> String {
+        String::from(match source {
+            EntropySource::System => "This computer's randomness",
+            EntropySource::Dice => "Dice rolls",
+            EntropySource::Coins => "Coin flips",
+        })
+    }
 }
 
 impl From<Entropy> for String {
@@ -108,3 +531,113 @@ impl From<Entropy> for String {
         })
     }
 }
+
+/// How soon a payment should confirm. Presets take their rate from the server's estimates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeSpeed {
+    Slow,
+    Normal,
+    Fast,
+    Custom,
+}
+
+impl FeeSpeed {
+    pub fn all() -> [FeeSpeed; 4] {
+        [
+            FeeSpeed::Slow,
+            FeeSpeed::Normal,
+            FeeSpeed::Fast,
+            FeeSpeed::Custom,
+        ]
+    }
+    pub fn presets() -> [FeeSpeed; 3] {
+        [FeeSpeed::Slow, FeeSpeed::Normal, FeeSpeed::Fast]
+    }
+    /// Confirmation target in blocks, as asked of `estimatefee`
+    pub fn target(&self) -> Option<usize> {
+        match self {
+            FeeSpeed::Slow => Some(24),
+            FeeSpeed::Normal => Some(6),
+            FeeSpeed::Fast => Some(2),
+            FeeSpeed::Custom => None,
+        }
+    }
+}
+
+impl Default for FeeSpeed {
+    fn default() -> Self {
+        FeeSpeed::Normal
+    }
+}
+
+impl From<FeeSpeed> for String {
+    fn from(speed: FeeSpeed) -> String {
+        String::from(match speed {
+            FeeSpeed::Slow => "Slow (about 4 hours)",
+            FeeSpeed::Normal => "Normal (about 1 hour)",
+            FeeSpeed::Fast => "Fast (about 20 minutes)",
+            FeeSpeed::Custom => "Custom",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    Satoshi,
+    Bitcoin,
+}
+
+impl Unit {
+    pub fn all() -> [Unit; 2] {
+        [Unit::Satoshi, Unit::Bitcoin]
+    }
+    pub fn format(&self, sats: i64) -> String {
+        let amount = SignedAmount::from_sat(sats);
+        match self {
+            Self::Satoshi => format!("{} sat", amount.to_string_in(Denomination::Satoshi)),
+            Self::Bitcoin => format!("{} BTC", amount.to_string_in(Denomination::Bitcoin)),
+        }
+    }
+}
+
+impl Default for Unit {
+    fn default() -> Self {
+        Unit::Satoshi
+    }
+}
+
+impl From<Unit> for String {
+    fn from(unit: Unit) -> String {
+        String::from(match unit {
+            Unit::Satoshi => "Satoshis",
+            Unit::Bitcoin => "Bitcoin",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn all() -> [Theme; 2] {
+        [Theme::Light, Theme::Dark]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::Light
+    }
+}
+
+impl From<Theme> for String {
+    fn from(theme: Theme) -> String {
+        String::from(match theme {
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        })
+    }
+}
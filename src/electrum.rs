@@ -0,0 +1,91 @@
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::hashes::{sha256, Hash};
+use magical_bitcoin_wallet::electrum_client::{ClientType, RawClient};
+use magical_bitcoin_wallet::Client as ElectrumClient;
+use rustls::{
+    Certificate, ClientConfig, ClientSession, RootCertStore, ServerCertVerified,
+    ServerCertVerifier, Session, StreamOwned, TLSError,
+};
+use socks::Socks5Stream;
+use webpki::DNSNameRef;
+
+use crate::config::Server;
+use crate::error::Error;
+
+/// Accepts exactly one certificate, identified by the SHA256 of its DER encoding
+struct PinnedCertificate {
+    fingerprint: Vec<u8>,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let certificate = presented_certs
+            .first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+        if sha256::Hash::hash(&certificate.0)[..] == self.fingerprint[..] {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TLSError::General(
+                "certificate doesn't match pinned fingerprint".to_string(),
+            ))
+        }
+    }
+}
+
+/// Connect to `server`, through its proxy if it has one
+pub fn connect(server: &Server) -> Result<ElectrumClient, Error> {
+    let proxy = server.proxy.as_deref();
+    match &server.certificate_fingerprint {
+        None => Ok(ElectrumClient::new(&server.url, proxy)?),
+        Some(fingerprint) => {
+            let address = server.url.strip_prefix("ssl://").ok_or_else(|| {
+                Error::Server("Pinned certificates need an ssl:// url".to_string())
+            })?;
+            let client = RawClient::from(pinned_stream(address, fingerprint, proxy)?);
+            Ok(ElectrumClient::from(ClientType::SSL(client)))
+        }
+    }
+}
+
+/// TLS stream to the server whose session only trusts the pinned certificate, instead of the
+/// system roots
+fn pinned_stream(
+    address: &str,
+    fingerprint: &str,
+    proxy: Option<&str>,
+) -> Result<StreamOwned<ClientSession, TcpStream>, Error> {
+    let fingerprint = Vec::<u8>::from_hex(fingerprint)
+        .map_err(|_| Error::Server("Invalid certificate fingerprint".to_string()))?;
+
+    let mut config = ClientConfig::new();
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(PinnedCertificate { fingerprint }));
+
+    // Self-signed servers are often addressed by IP, and the pin makes the name irrelevant
+    let host = address.rsplitn(2, ':').last().unwrap_or(address);
+    let dns_name = DNSNameRef::try_from_ascii_str(host)
+        .unwrap_or_else(|_| DNSNameRef::try_from_ascii_str("electrum").unwrap());
+    let mut session = ClientSession::new(&Arc::new(config), dns_name);
+
+    let mut socket = match proxy {
+        Some(proxy) => Socks5Stream::connect(proxy, address)?.into_inner(),
+        None => TcpStream::connect(address)?,
+    };
+    // Handshake up front, so a wrong certificate fails here rather than on the first request
+    while session.is_handshaking() {
+        session
+            .complete_io(&mut socket)
+            .map_err(|e| Error::Server(format!("{}", e)))?;
+    }
+    Ok(StreamOwned::new(session, socket))
+}
@@ -0,0 +1,110 @@
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::aes::KeySize;
+use crypto::aes_gcm::AesGcm;
+use crypto::scrypt::{scrypt, ScryptParams};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{consume_library_error, Error};
+
+// scrypt with N = 2^15, r = 8 needs 32 MiB per derivation
+const LOG_N: u8 = 15;
+const R: u32 = 8;
+const P: u32 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Secret encrypted with AES-256-GCM under a scrypt-derived key. The KDF parameters are stored
+/// alongside so they can be raised later without breaking existing accounts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Encrypted {
+    log_n: u8,
+    r: u32,
+    p: u32,
+    salt: String,
+    nonce: String,
+    tag: String,
+    ciphertext: String,
+}
+
+fn derive_key(password: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    let params = ScryptParams::new(log_n, r, p);
+    scrypt(password.as_bytes(), salt, &params, &mut key);
+    key
+}
+
+pub fn encrypt(plaintext: &[u8], password: &str) -> Encrypted {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    thread_rng().fill_bytes(&mut salt);
+    thread_rng().fill_bytes(&mut nonce);
+
+    let key = derive_key(password, &salt, LOG_N, R, P);
+    let mut ciphertext = vec![0u8; plaintext.len()];
+    let mut tag = [0u8; TAG_LEN];
+    AesGcm::new(KeySize::KeySize256, &key, &nonce, &[]).encrypt(
+        plaintext,
+        &mut ciphertext,
+        &mut tag,
+    );
+
+    Encrypted {
+        log_n: LOG_N,
+        r: R,
+        p: P,
+        salt: salt.to_hex(),
+        nonce: nonce.to_hex(),
+        tag: tag.to_hex(),
+        ciphertext: ciphertext.to_hex(),
+    }
+}
+
+/// Fails with `Error::WrongPassword` if the authentication tag doesn't match
+pub fn decrypt(encrypted: &Encrypted, password: &str) -> Result<Vec<u8>, Error> {
+    let salt = Vec::<u8>::from_hex(&encrypted.salt).map_err(consume_library_error)?;
+    let nonce = Vec::<u8>::from_hex(&encrypted.nonce).map_err(consume_library_error)?;
+    let tag = Vec::<u8>::from_hex(&encrypted.tag).map_err(consume_library_error)?;
+    let ciphertext = Vec::<u8>::from_hex(&encrypted.ciphertext).map_err(consume_library_error)?;
+
+    let key = derive_key(password, &salt, encrypted.log_n, encrypted.r, encrypted.p);
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    let authentic = AesGcm::new(KeySize::KeySize256, &key, &nonce, &[]).decrypt(
+        &ciphertext,
+        &mut plaintext,
+        &tag,
+    );
+    if authentic {
+        Ok(plaintext)
+    } else {
+        Err(Error::WrongPassword)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let encrypted = encrypt(b"wpkh(tprv/0/*)", "correct horse");
+        assert_ne!(encrypted.ciphertext, b"wpkh(tprv/0/*)".to_hex());
+        assert_eq!(
+            decrypt(&encrypted, "correct horse").unwrap(),
+            b"wpkh(tprv/0/*)".to_vec()
+        );
+    }
+
+    #[test]
+    fn wrong_password() {
+        let encrypted = encrypt(b"wpkh(tprv/0/*)", "correct horse");
+        assert!(matches!(
+            decrypt(&encrypted, "battery staple"),
+            Err(Error::WrongPassword)
+        ));
+        assert!(matches!(decrypt(&encrypted, ""), Err(Error::WrongPassword)));
+    }
+}
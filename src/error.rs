@@ -6,8 +6,22 @@ extern crate bitcoin;
 pub enum Error {
     BroadcastError,
     Mnemonic(String),
+    /// Zero-based position of a word that isn't in the word list
+    InvalidWord(usize),
+    InvalidChecksum,
+    /// Zero-based position, in the input as typed, of a character that isn't a die face or coin
+    /// side
+    InvalidRoll(usize),
     AddressDerivationError,
     CouldNotFinalize,
+    WrongPassword,
+    WatchOnly,
+    Import(String),
+    /// SLIP-39 shares that can't be split or combined
+    Shamir(String),
+    Server(String),
+    /// A file couldn't be read or written
+    File(String),
 
     // Hack b/c Iced needs cloneable Error, so we can't wrap non-cloneable library errors
     Library(String),
@@ -28,7 +42,23 @@ macro_rules! impl_error {
     };
 }
 
-impl_error!(magical_bitcoin_wallet::error::Error, Magical);
-impl_error!(magical_bitcoin_wallet::electrum_client::Error, Electrum);
+// Connection failures become `Server` so callers can tell them from the wallet refusing a request
+impl std::convert::From<magical_bitcoin_wallet::error::Error> for Error {
+    fn from(err: magical_bitcoin_wallet::error::Error) -> Self {
+        match err {
+            magical_bitcoin_wallet::error::Error::Electrum(e) => Error::Server(format!("{:?}", e)),
+            e => Error::Library(format!("{:?}", e)),
+        }
+    }
+}
+
+impl std::convert::From<magical_bitcoin_wallet::electrum_client::Error> for Error {
+    fn from(err: magical_bitcoin_wallet::electrum_client::Error) -> Self {
+        Error::Server(format!("{:?}", err))
+    }
+}
+
 impl_error!(magical_bitcoin_wallet::sled::Error, Sled);
 impl_error!(bitcoin::Error, Bitcoin);
+impl_error!(std::io::Error, Io);
+impl_error!(serde_json::Error, Json);
@@ -1,31 +1,45 @@
 use iced::{
-    button, executor, Application, Button, Column, Command, Element, HorizontalAlignment, Row,
-    Settings, Text,
+    button, executor, Application, Button, Column, Command, Container, Element,
+    HorizontalAlignment, Length, Row, Settings, Text,
 };
 use log::error;
+use std::time::Duration;
+use uuid::Uuid;
 
 mod account;
+mod backup;
+mod config;
 mod data;
+mod electrum;
+mod encryption;
 mod error;
-mod mocks;
 mod receive;
 mod send;
+mod settings;
 mod setup;
+mod shamir;
+mod store;
+mod style;
 mod tasks;
 mod transactions;
 mod utils;
+mod wallets;
 
 use data::Account;
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Setup(setup::Message),
-    // TODO: this should just take an account UUID ...
-    ChangeAccount(Account),
+    ChangeAccount(Uuid),
     CreateAccount,
     AccountMessage(account::Message),
+    // Numbered so only the latest label edit writes the registry, once typing pauses
+    SaveLabels(usize),
 }
 
+/// How long typing in a coin label has to pause before the registry is rewritten with it
+const LABEL_DELAY: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
 enum Page {
     Setup(setup::Page),
@@ -36,6 +50,7 @@ struct Junction {
     page: Page,
     accounts: Vec<Account>,
     new_account_button: button::State,
+    label_edits: usize,
 }
 
 impl<'a> Junction {
@@ -47,13 +62,11 @@ impl<'a> Junction {
 
         // Existing accounts
         for account in accounts {
-            // FIXME: don't clone this
-            let cloned = Account::clone(account);
             let tab = Button::new(
                 &mut account.tab_button,
                 Text::new(account.name.clone()).horizontal_alignment(HorizontalAlignment::Center),
             )
-            .on_press(Message::ChangeAccount(cloned));
+            .on_press(Message::ChangeAccount(account.id));
             tabs = tabs.push(tab);
         }
 
@@ -76,14 +89,31 @@ impl Application for Junction {
     type Flags = ();
 
     fn new(_flags: ()) -> (Self, Command<Message>) {
-        let accounts = mocks::make_accounts(3);
-        let account = accounts[0].clone();
+        // Refuse to start rather than overwrite a registry we couldn't read
+        let accounts = store::load_accounts().expect("couldn't load accounts");
+
+        // First launch: go straight to the setup wizard
+        let account = match accounts.first() {
+            Some(account) => account.clone(),
+            None => {
+                return (
+                    Self {
+                        page: Page::Setup(setup::Page::new(vec![])),
+                        accounts,
+                        new_account_button: button::State::new(),
+                        label_edits: 0,
+                    },
+                    Command::none(),
+                )
+            }
+        };
+
         (
             Self {
-                //page: Page::Setup(setup::Page::new()),
                 page: Page::Account(account::Page::new(account.clone())),
                 accounts,
                 new_account_button: button::State::new(),
+                label_edits: 0,
             },
             // Fetch balances / transactions
             Command::perform(
@@ -102,12 +132,82 @@ impl Application for Junction {
         match message {
             Message::AccountMessage(msg) => {
                 if let Page::Account(ref mut account_page) = self.page {
+                    // Intercept account edits so the registry and tabs stay in sync
+                    if let account::Message::Settings(settings::Message::SaveAccount(ref edited)) =
+                        msg
+                    {
+                        // The settings form checks this too, but the registry must never hold
+                        // an unnamed account
+                        if edited.name.trim().is_empty() {
+                            error!("Refusing to save account {} without a name", edited.id);
+                            return Command::none();
+                        }
+                        if let Some(account) = self.accounts.iter_mut().find(|a| a.id == edited.id)
+                        {
+                            account.name = edited.name.clone();
+                            account.watched_addresses = edited.watched_addresses;
+                        }
+                        if let Err(e) = store::save_accounts(&self.accounts) {
+                            error!("Couldn't save accounts: {:?}", e);
+                        }
+                    }
+                    if let account::Message::Settings(settings::Message::AccountEncrypted(
+                        ref encrypted,
+                    )) = msg
+                    {
+                        if let Some(account) =
+                            self.accounts.iter_mut().find(|a| a.id == encrypted.id)
+                        {
+                            account.descriptor = encrypted.descriptor.clone();
+                            account.change_descriptor = encrypted.change_descriptor.clone();
+                            account.encrypted_descriptor = encrypted.encrypted_descriptor.clone();
+                        }
+                        if let Err(e) = store::save_accounts(&self.accounts) {
+                            error!("Couldn't save accounts: {:?}", e);
+                        }
+                    }
+                    if let account::Message::Send(send::Message::SaveLabel(
+                        ref outpoint,
+                        ref label,
+                    )) = msg
+                    {
+                        if let Some(account) =
+                            self.accounts.iter_mut().find(|a| a.id == account_page.id())
+                        {
+                            account.set_label(outpoint, label);
+                        }
+                        // Sent on every keystroke, so the write waits until typing pauses. Kept
+                        // here rather than in the send page so leaving it doesn't drop the label.
+                        self.label_edits += 1;
+                        let edit = self.label_edits;
+                        return Command::batch(vec![
+                            account_page.update(msg).map(Message::AccountMessage),
+                            Command::perform(tokio::time::delay_for(LABEL_DELAY), move |_| {
+                                Message::SaveLabels(edit)
+                            }),
+                        ]);
+                    }
                     account_page.update(msg).map(Message::AccountMessage)
                 } else {
                     Command::none()
                 }
             }
-            Message::ChangeAccount(account) => {
+            Message::SaveLabels(edit) => {
+                if edit == self.label_edits {
+                    if let Err(e) = store::save_accounts(&self.accounts) {
+                        error!("Couldn't save accounts: {:?}", e);
+                    }
+                }
+                Command::none()
+            }
+            Message::ChangeAccount(id) => {
+                let account = match self.accounts.iter().find(|a| a.id == id) {
+                    Some(account) => account.clone(),
+                    None => {
+                        error!("No account with id {}", id);
+                        return Command::none();
+                    }
+                };
                 self.page = Page::Account(account::Page::new(account.clone()));
                 Command::perform(
                     tasks::update_account(account),
@@ -116,15 +216,29 @@ impl Application for Junction {
                 .map(Message::AccountMessage)
             }
             Message::CreateAccount => {
-                self.page = Page::Setup(setup::Page::new());
+                self.page = Page::Setup(setup::Page::new(self.accounts.clone()));
                 Command::none()
             }
             Message::Setup(msg) => match self.page {
                 Page::Setup(ref mut page) => match msg {
                     // Intercept account completion
                     setup::Message::SetupComplete(account) => {
-                        self.page = Page::Account(account::Page::new(account));
-                        Command::none()
+                        // The wizard checks this too, but the registry must never hold an
+                        // unnamed account
+                        if account.name.trim().is_empty() {
+                            error!("Refusing to save account {} without a name", account.id);
+                            return Command::none();
+                        }
+                        self.accounts.push(account.clone());
+                        if let Err(e) = store::save_accounts(&self.accounts) {
+                            error!("Couldn't save accounts: {:?}", e);
+                        }
+                        self.page = Page::Account(account::Page::new(account.clone()));
+                        Command::perform(
+                            tasks::update_account(account),
+                            account::Message::AccountUpdated,
+                        )
+                        .map(Message::AccountMessage)
                     }
                     // Forward all other setup wizard events
                     _ => page.update(msg).map(Message::Setup),
@@ -138,17 +252,23 @@ impl Application for Junction {
     }
 
     fn view(&mut self) -> Element<Message> {
-        match self.page {
+        let content: Element<_> = match self.page {
             Page::Setup(ref mut setup_page) => setup_page.view().map(Message::Setup),
             Page::Account(ref mut account_page) => Column::new()
                 .push(Self::tabs(&mut self.accounts, &mut self.new_account_button))
                 .push(account_page.view().map(Message::AccountMessage))
                 .into(),
-        }
+        };
+        Container::new(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(config::get().theme)
+            .into()
     }
 }
 
 pub fn main() {
     env_logger::init();
+    config::init(config::Config::load().expect("couldn't load config"));
     Junction::run(Settings::default())
 }
@@ -1,37 +1,187 @@
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use iced::{
-    button, scrollable, text_input, Align, Button, Column, Command, Element, HorizontalAlignment,
-    Text, TextInput,
+    button, scrollable, text_input, Align, Button, Checkbox, Column, Command, Element,
+    HorizontalAlignment, Length, Radio, Row, Scrollable, Text, TextInput,
 };
 
-use crate::data::Account;
+use crate::config;
+use crate::data::{Account, Coin, FeeSpeed, Signed};
 use crate::error::Error;
-use crate::tasks;
+use crate::tasks::{self, MIN_FEE_RATE};
+use crate::utils::{address_is_for, estimated_vsize};
+use bitcoin::consensus::encode::serialize;
 use bitcoin::util::amount::Denomination;
-use bitcoin::{Address, Amount, Txid};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{Address, Amount, OutPoint, Script, Txid};
+use magical_bitcoin_wallet::types::TransactionDetails;
+
+use clipboard::{ClipboardContext, ClipboardProvider};
+use log::warn;
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    Address(String),
-    Amount(String),
+    Address(usize, String),
+    Amount(usize, String),
+    Label(usize, String),
+    AddRecipient,
+    RemoveRecipient(usize),
+    SendAll(bool),
+    CsvPath(String),
+    ImportCsv,
+    CsvImported(Result<Vec<(String, String, String)>, Error>),
+    Password(String),
     //Broadcast,
     //BroadcastResult(Result<Txid, Error>),
     AccountUpdated(Account),
-    Send,
-    SendResult(Result<Txid, Error>),
+    FeesEstimated(Result<Vec<Option<f32>>, Error>),
+    FeeSpeedSelected(FeeSpeed),
+    CustomFeeRate(String),
+    // Numbered so only the latest edit gets previewed, once typing pauses
+    PreviewDue(usize),
+    // Numbered so only the latest preview is shown. Fee, what the recipient gets, then the coins
+    // spent.
+    FeeCalculated(usize, Result<(u64, u64, Vec<OutPoint>), Error>),
+    ToggleCoins,
+    CoinsLoaded(Result<Vec<Coin>, Error>),
+    CoinSelected(usize, bool),
+    AddMoreCoins(bool),
+    CoinLabel(usize, String),
+    // Intercepted by main to save the account registry
+    SaveLabel(OutPoint, String),
+    Review,
+    // Intercepted by the page to start the review, with the fee rate it was built at
+    PsbtBuilt(
+        f32,
+        Result<(PartiallySignedTransaction, TransactionDetails), Error>,
+    ),
+    Confirm,
+    Cancel,
+    SendResult(Result<Signed, Error>),
+    CopyPsbt,
+}
+
+/// How long typing has to pause before the payment is built to preview its fee. Every build
+/// reserves a change address and holds the wallet, so not on every keystroke.
+const PREVIEW_DELAY: Duration = Duration::from_millis(500);
+
+/// Rate of a preset speed, once the server's estimates arrived and if it had one
+fn preset_rate(rates: &Option<Vec<Option<f32>>>, speed: FeeSpeed) -> Option<f32> {
+    let index = FeeSpeed::presets().iter().position(|&s| s == speed)?;
+    rates.as_ref()?.get(index).cloned().flatten()
+}
+
+/// Put `text` on the system clipboard, which isn't always there, e.g. on headless machines
+fn copy_to_clipboard(text: String) -> Result<(), String> {
+    let mut ctx: ClipboardContext = ClipboardProvider::new().map_err(|e| e.to_string())?;
+    ctx.set_contents(text).map_err(|e| e.to_string())
+}
+
+/// Total a transaction pays to `address`
+fn paid_to(psbt: &PartiallySignedTransaction, address: &Address) -> u64 {
+    let script = address.script_pubkey();
+    psbt.global
+        .unsigned_tx
+        .output
+        .iter()
+        .filter(|output| output.script_pubkey == script)
+        .map(|output| output.value)
+        .sum()
 }
 
+/// One payment in the list, as typed or imported
 #[derive(Debug, Clone)]
-pub struct AddressAndAmount {
-    broadcast_button: button::State,
+struct Recipient {
     address_input: text_input::State,
     address: String,
     amount_input: text_input::State,
     amount: String,
-    txid: Option<Txid>,
+    label_input: text_input::State,
+    /// The payer's own note, e.g. who gets paid
+    label: String,
+    remove_button: button::State,
+}
+
+impl Recipient {
+    fn new(address: String, amount: String, label: String) -> Self {
+        Self {
+            address_input: text_input::State::new(),
+            address,
+            amount_input: text_input::State::new(),
+            amount,
+            label_input: text_input::State::new(),
+            label,
+            remove_button: button::State::new(),
+        }
+    }
+    fn is_blank(&self) -> bool {
+        self.address.trim().is_empty() && self.amount.trim().is_empty()
+    }
+}
+
+/// A coin in the picker, with its label as being edited
+#[derive(Debug, Clone)]
+struct CoinRow {
+    coin: Coin,
+    selected: bool,
+    label_input: text_input::State,
+    label: String,
+}
+
+fn describe_coin(coin: &Coin) -> String {
+    let confirmations = match coin.confirmations {
+        0 => "unconfirmed".to_string(),
+        1 => "1 confirmation".to_string(),
+        n => format!("{} confirmations", n),
+    };
+    let address = match &coin.address {
+        Some(address) => address.to_string(),
+        None => "unknown script".to_string(),
+    };
+    format!(
+        "{} · {} · {}",
+        config::get().unit.format(coin.amount as i64),
+        confirmations,
+        address
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct AddressAndAmount {
+    broadcast_button: button::State,
+    recipients: Vec<Recipient>,
+    add_button: button::State,
+    /// Send every spendable or selected coin, less the fee, to the only recipient
+    send_all: bool,
+    csv_path_input: text_input::State,
+    csv_path: String,
+    import_button: button::State,
+    import_message: Option<String>,
+    fee_speed: FeeSpeed,
+    /// Server estimates for each preset speed, in sat/vB, None where it had none
+    fee_rates: Option<Vec<Option<f32>>>,
+    custom_fee_rate_input: text_input::State,
+    custom_fee_rate: String,
+    /// Absolute fee of the payment as currently entered
+    fee: Option<u64>,
+    /// What the recipient gets after the fee, when sending everything
+    received: Option<u64>,
+    /// Coins the payment as currently entered spends, selected or not
+    spent: Option<Vec<OutPoint>>,
+    fee_error: Option<String>,
+    previews: usize,
+    coins_button: button::State,
+    /// Coin control only applies while the picker is open
+    show_coins: bool,
+    coins: Option<Vec<CoinRow>>,
+    coins_error: Option<String>,
+    /// Let the wallet add other coins when the selected ones don't cover the payment. Off by
+    /// default, since coin control is mostly about not mixing coins from different sources.
+    add_more_coins: bool,
     error: Option<String>,
-    sending: bool,
+    building: bool,
     account: Account,
 }
 
@@ -39,108 +189,973 @@ impl AddressAndAmount {
     pub fn new(account: Account) -> Self {
         Self {
             broadcast_button: button::State::new(),
-            address: "".to_string(),
-            address_input: text_input::State::new(),
-            amount: "".to_string(),
-            amount_input: text_input::State::new(),
-            txid: None,
+            recipients: vec![Recipient::new(
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )],
+            add_button: button::State::new(),
+            send_all: false,
+            csv_path_input: text_input::State::new(),
+            csv_path: "".to_string(),
+            import_button: button::State::new(),
+            import_message: None,
+            fee_speed: FeeSpeed::default(),
+            fee_rates: None,
+            custom_fee_rate_input: text_input::State::new(),
+            custom_fee_rate: "".to_string(),
+            fee: None,
+            received: None,
+            spent: None,
+            fee_error: None,
+            previews: 0,
+            coins_button: button::State::new(),
+            show_coins: false,
+            coins: None,
+            coins_error: None,
+            add_more_coins: false,
             error: None,
-            sending: false,
+            building: false,
             account,
         }
     }
+    /// A recipient's address and amount as typed, checked against this account's network
+    fn payment(&self, recipient: &Recipient) -> Result<(Address, Amount), String> {
+        let address =
+            Address::from_str(recipient.address.trim()).map_err(|_| "Bad address".to_string())?;
+        if !address_is_for(&address, self.account.network) {
+            return Err(format!(
+                "That address is for {}, this account is on {}",
+                address.network, self.account.network
+            ));
+        }
+        // The amount is worked out from the coins spent
+        if self.send_all {
+            return Ok((address, Amount::from_sat(0)));
+        }
+        let amount = Amount::from_str_in(recipient.amount.trim(), Denomination::Satoshi)
+            .map_err(|_| "Bad amount".to_string())?;
+        if amount == Amount::from_sat(0) {
+            return Err("Amount must be more than zero".to_string());
+        }
+        Ok((address, amount))
+    }
+    /// Every recipient's payment, or what is wrong with each bad one
+    fn payments(&self) -> Result<Vec<(Address, Amount)>, String> {
+        let mut payments = vec![];
+        let mut errors = vec![];
+        for (i, recipient) in self.recipients.iter().enumerate() {
+            match self.payment(recipient) {
+                Ok(payment) => payments.push(payment),
+                Err(e) if self.recipients.len() == 1 => errors.push(e),
+                Err(e) => errors.push(format!("Recipient {}: {}", i + 1, e)),
+            }
+        }
+        if self.send_all && self.recipients.len() > 1 {
+            errors.push("Max only works with a single recipient".to_string());
+        }
+        if self.recipients.is_empty() {
+            errors.push("Add a recipient".to_string());
+        }
+        if errors.is_empty() {
+            Ok(payments)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+    /// Chosen fee rate in sat/vB
+    fn fee_rate(&self) -> Result<f32, String> {
+        match self.fee_speed {
+            FeeSpeed::Custom => self
+                .custom_fee_rate
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|rate| rate.is_finite() && *rate >= MIN_FEE_RATE)
+                .ok_or_else(|| format!("Fee rate must be at least {} sat/vB", MIN_FEE_RATE)),
+            speed => preset_rate(&self.fee_rates, speed)
+                .ok_or_else(|| "No fee estimates from the server, enter a custom rate".to_string()),
+        }
+    }
+    /// Outpoints picked in the coin picker, empty for automatic selection
+    fn selected_coins(&self) -> Vec<OutPoint> {
+        match &self.coins {
+            Some(rows) if self.show_coins => rows
+                .iter()
+                .filter(|row| row.selected)
+                .map(|row| row.coin.outpoint)
+                .collect(),
+            _ => vec![],
+        }
+    }
+    /// Sending everything from a selection must not pull in other coins
+    fn spend_only_selected(&self) -> bool {
+        !self.add_more_coins || self.send_all
+    }
+    fn load_coins(&self) -> Command<Message> {
+        Command::perform(
+            tasks::list_coins(self.account.clone()),
+            Message::CoinsLoaded,
+        )
+    }
+    /// Forget the fee shown and, once the payment parses and stops changing, preview it again
+    fn preview(&mut self) -> Command<Message> {
+        self.fee = None;
+        self.received = None;
+        self.spent = None;
+        self.fee_error = None;
+        self.previews += 1;
+        let preview = self.previews;
+        match (self.payments(), self.fee_rate()) {
+            (Ok(_), Ok(_)) => Command::perform(tokio::time::delay_for(PREVIEW_DELAY), move |_| {
+                Message::PreviewDue(preview)
+            }),
+            _ => Command::none(),
+        }
+    }
+    /// Build the payment as entered without signing it, to show its fee
+    fn build_preview(&self) -> Command<Message> {
+        let preview = self.previews;
+        match (self.payments(), self.fee_rate()) {
+            (Ok(payments), Ok(fee_rate)) => {
+                // Only read when sending everything, to the only recipient
+                let address = payments[0].0.clone();
+                Command::perform(
+                    tasks::create_psbt(
+                        self.account.clone(),
+                        payments,
+                        self.send_all,
+                        fee_rate,
+                        self.selected_coins(),
+                        self.spend_only_selected(),
+                    ),
+                    move |result| {
+                        Message::FeeCalculated(
+                            preview,
+                            result.map(|(psbt, details)| {
+                                let spent = psbt
+                                    .global
+                                    .unsigned_tx
+                                    .input
+                                    .iter()
+                                    .map(|input| input.previous_output)
+                                    .collect();
+                                (details.fees, paid_to(&psbt, &address), spent)
+                            }),
+                        )
+                    },
+                )
+            }
+            _ => Command::none(),
+        }
+    }
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::Address(address) => {
-                self.address = address;
+            Message::Address(index, address) => {
+                if let Some(recipient) = self.recipients.get_mut(index) {
+                    recipient.address = address;
+                }
+                self.error = None;
+                self.preview()
+            }
+            Message::Amount(index, amount) => {
+                if let Some(recipient) = self.recipients.get_mut(index) {
+                    recipient.amount = amount;
+                }
+                self.error = None;
+                self.preview()
+            }
+            Message::Label(index, label) => {
+                if let Some(recipient) = self.recipients.get_mut(index) {
+                    recipient.label = label;
+                }
                 Command::none()
             }
-            Message::Amount(amount) => {
-                self.amount = amount;
+            Message::AddRecipient => {
+                self.recipients.push(Recipient::new(
+                    "".to_string(),
+                    "".to_string(),
+                    "".to_string(),
+                ));
+                self.send_all = false;
                 self.error = None;
+                self.preview()
+            }
+            Message::RemoveRecipient(index) => {
+                if self.recipients.len() > 1 && index < self.recipients.len() {
+                    self.recipients.remove(index);
+                }
+                self.error = None;
+                self.preview()
+            }
+            Message::SendAll(send_all) => {
+                self.send_all = send_all;
+                self.error = None;
+                self.preview()
+            }
+            Message::CsvPath(path) => {
+                self.csv_path = path;
+                self.import_message = None;
                 Command::none()
             }
+            Message::ImportCsv => Command::perform(
+                tasks::read_recipients(PathBuf::from(self.csv_path.trim())),
+                Message::CsvImported,
+            ),
+            Message::CsvImported(result) => {
+                match result {
+                    Ok(rows) => {
+                        self.import_message = Some(format!("Imported {} recipients", rows.len()));
+                        self.recipients.retain(|recipient| !recipient.is_blank());
+                        self.recipients
+                            .extend(rows.into_iter().map(|(address, amount, label)| {
+                                Recipient::new(address, amount, label)
+                            }));
+                        if self.recipients.is_empty() {
+                            self.recipients.push(Recipient::new(
+                                "".to_string(),
+                                "".to_string(),
+                                "".to_string(),
+                            ));
+                        }
+                        self.send_all = false;
+                        // Point out bad rows straight away rather than at Send
+                        self.error = self.payments().err();
+                    }
+                    Err(Error::File(e)) => {
+                        self.import_message = Some(format!("Couldn't import: {}", e))
+                    }
+                    Err(e) => self.import_message = Some(format!("Couldn't import: {:?}", e)),
+                }
+                self.preview()
+            }
             Message::AccountUpdated(account) => {
                 self.account = account;
                 self.error = None;
-                Command::none()
+                if self.coins.is_some() {
+                    Command::batch(vec![self.preview(), self.load_coins()])
+                } else {
+                    self.preview()
+                }
             }
-            Message::Send => {
-                self.sending = true;
-                if let Ok(address) = Address::from_str(&self.address) {
-                    if let Ok(amount) = Amount::from_str_in(&self.amount, Denomination::Satoshi) {
-                        return Command::perform(
-                            tasks::send_money(self.account.clone(), address, amount),
-                            Message::SendResult,
-                        );
-                    } else {
-                        self.error = Some("Bad amount".to_string());
+            Message::FeesEstimated(result) => {
+                match result {
+                    Ok(rates) => {
+                        self.fee_rates = Some(rates);
+                        // A preset the server can't estimate can't be sent at
+                        if self.fee_speed != FeeSpeed::Custom
+                            && preset_rate(&self.fee_rates, self.fee_speed).is_none()
+                        {
+                            self.fee_speed = FeeSpeed::Custom;
+                        }
+                    }
+                    Err(e) => {
+                        // Custom rates still work without the server
+                        warn!("Couldn't estimate fees: {:?}", e);
+                        self.fee_speed = FeeSpeed::Custom;
                     }
+                }
+                self.preview()
+            }
+            Message::FeeSpeedSelected(speed) => {
+                self.fee_speed = speed;
+                self.error = None;
+                self.preview()
+            }
+            Message::CustomFeeRate(rate) => {
+                self.custom_fee_rate = rate;
+                self.error = None;
+                self.preview()
+            }
+            Message::PreviewDue(preview) => {
+                if preview == self.previews {
+                    self.build_preview()
                 } else {
-                    self.error = Some("Bad address".to_string())
+                    Command::none()
+                }
+            }
+            Message::FeeCalculated(preview, result) => {
+                if preview == self.previews {
+                    match result {
+                        Ok((fee, received, spent)) => {
+                            self.fee = Some(fee);
+                            self.received = Some(received);
+                            self.spent = Some(spent);
+                        }
+                        Err(e) => self.fee_error = Some(format!("Can't pay this: {:?}", e)),
+                    }
                 }
                 Command::none()
             }
-            Message::SendResult(result) => {
-                self.sending = false;
+            Message::ToggleCoins => {
+                self.show_coins = !self.show_coins;
+                self.error = None;
+                if self.show_coins && self.coins.is_none() {
+                    self.load_coins()
+                } else {
+                    self.preview()
+                }
+            }
+            Message::CoinsLoaded(result) => {
                 match result {
-                    Ok(txid) => self.txid = Some(txid),
-                    Err(_) => self.error = Some("Could not sign".to_string()),
+                    Ok(coins) => {
+                        // Keep the selection across refreshes, minus coins spent meanwhile
+                        let selected: Vec<OutPoint> = match &self.coins {
+                            Some(rows) => rows
+                                .iter()
+                                .filter(|row| row.selected)
+                                .map(|row| row.coin.outpoint)
+                                .collect(),
+                            None => vec![],
+                        };
+                        let account = &self.account;
+                        self.coins = Some(
+                            coins
+                                .into_iter()
+                                .map(|coin| CoinRow {
+                                    selected: selected.contains(&coin.outpoint),
+                                    label_input: text_input::State::new(),
+                                    label: account
+                                        .label(&coin.outpoint)
+                                        .cloned()
+                                        .unwrap_or_default(),
+                                    coin,
+                                })
+                                .collect(),
+                        );
+                        self.coins_error = None;
+                    }
+                    Err(e) => self.coins_error = Some(format!("Couldn't list coins: {:?}", e)),
+                }
+                self.preview()
+            }
+            Message::CoinSelected(index, selected) => {
+                if let Some(row) = self.coins.as_mut().and_then(|rows| rows.get_mut(index)) {
+                    row.selected = selected;
+                }
+                self.error = None;
+                self.preview()
+            }
+            Message::AddMoreCoins(add_more_coins) => {
+                self.add_more_coins = add_more_coins;
+                self.error = None;
+                self.preview()
+            }
+            Message::CoinLabel(index, label) => {
+                match self.coins.as_mut().and_then(|rows| rows.get_mut(index)) {
+                    Some(row) => {
+                        row.label = label.clone();
+                        // Saved as typed, so leaving the page never loses a label
+                        let outpoint = row.coin.outpoint;
+                        Command::perform(async move { (outpoint, label) }, |(outpoint, label)| {
+                            Message::SaveLabel(outpoint, label)
+                        })
+                    }
+                    None => Command::none(),
+                }
+            }
+            Message::SaveLabel(outpoint, label) => {
+                self.account.set_label(&outpoint, &label);
+                Command::none()
+            }
+            Message::Review => {
+                let payments = match self.payments() {
+                    Ok(payments) => payments,
+                    Err(message) => {
+                        self.error = Some(message);
+                        return Command::none();
+                    }
+                };
+                let fee_rate = match self.fee_rate() {
+                    Ok(fee_rate) => fee_rate,
+                    Err(message) => {
+                        self.error = Some(message);
+                        return Command::none();
+                    }
+                };
+                self.building = true;
+                Command::perform(
+                    tasks::create_psbt(
+                        self.account.clone(),
+                        payments,
+                        self.send_all,
+                        fee_rate,
+                        self.selected_coins(),
+                        self.spend_only_selected(),
+                    ),
+                    move |result| Message::PsbtBuilt(fee_rate, result),
+                )
+            }
+            Message::PsbtBuilt(_, result) => {
+                self.building = false;
+                if let Err(e) = result {
+                    self.error = Some(format!("Could not create the transaction: {:?}", e));
                 }
                 Command::none()
             }
+            // Only the review step sends these
+            Message::Password(_)
+            | Message::Confirm
+            | Message::Cancel
+            | Message::SendResult(_)
+            | Message::CopyPsbt => Command::none(),
         }
     }
     pub fn view(&mut self) -> Element<Message> {
-        if let Some(txid) = self.txid.clone() {
-            Text::new(txid.to_string()).into()
+        let send_all = self.send_all;
+        let count = self.recipients.len();
+        let everything = if self.selected_coins().is_empty() {
+            "Whole balance, less the fee"
         } else {
-            let address_input = TextInput::new(
-                &mut self.address_input,
-                "Address",
-                &self.address,
-                Message::Address,
+            "All selected coins, less the fee"
+        };
+        let total = match self.payments() {
+            Ok(payments) if count > 1 => Some(
+                payments
+                    .iter()
+                    .map(|(_, amount)| amount.as_sat())
+                    .sum::<u64>(),
+            ),
+            _ => None,
+        };
+        let recipients = self.recipients.iter_mut().enumerate().fold(
+            Column::new().spacing(10),
+            |recipients, (i, recipient)| {
+                let amount: Element<_> = if send_all {
+                    Text::new(everything).width(Length::Units(200)).into()
+                } else {
+                    TextInput::new(
+                        &mut recipient.amount_input,
+                        "Amount (sats)",
+                        &recipient.amount,
+                        move |amount| Message::Amount(i, amount),
+                    )
+                    .padding(15)
+                    .width(Length::Units(200))
+                    .into()
+                };
+                let mut row = Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        TextInput::new(
+                            &mut recipient.address_input,
+                            "Address",
+                            &recipient.address,
+                            move |address| Message::Address(i, address),
+                        )
+                        .padding(15),
+                    )
+                    .push(amount)
+                    .push(
+                        TextInput::new(
+                            &mut recipient.label_input,
+                            "Label",
+                            &recipient.label,
+                            move |label| Message::Label(i, label),
+                        )
+                        .padding(15)
+                        .width(Length::Units(150)),
+                    );
+                if count > 1 {
+                    row = row.push(
+                        Button::new(&mut recipient.remove_button, Text::new("Remove"))
+                            .on_press(Message::RemoveRecipient(i)),
+                    );
+                }
+                recipients.push(row)
+            },
+        );
+
+        let mut list_buttons = Row::new().spacing(10).align_items(Align::Center).push(
+            Button::new(&mut self.add_button, Text::new("Add recipient"))
+                .on_press(Message::AddRecipient),
+        );
+        // Sending everything needs a single output to take the fee from
+        if count == 1 {
+            list_buttons = list_buttons.push(Checkbox::new(send_all, "Max", Message::SendAll));
+        }
+
+        let import = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(
+                TextInput::new(
+                    &mut self.csv_path_input,
+                    "CSV file of address, amount in sats, label",
+                    &self.csv_path,
+                    Message::CsvPath,
+                )
+                .padding(15),
             )
-            .padding(15);
+            .push(
+                Button::new(&mut self.import_button, Text::new("Import CSV"))
+                    .on_press(Message::ImportCsv),
+            );
+
+        let coins_label = if self.show_coins {
+            "Pick coins automatically"
+        } else {
+            "Choose coins"
+        };
+        let coins_button = Button::new(&mut self.coins_button, Text::new(coins_label))
+            .on_press(Message::ToggleCoins);
+
+        let selected = self.selected_coins();
+        let coins: Option<Element<_>> = if !self.show_coins {
+            None
+        } else if let Some(error) = self.coins_error.clone() {
+            Some(Text::new(error).into())
+        } else {
+            match self.coins {
+                None => Some(Text::new("Loading coins").into()),
+                Some(ref rows) if rows.is_empty() => Some(Text::new("No coins to spend").into()),
+                Some(ref mut rows) => {
+                    let spent = self.spent.clone().unwrap_or_default();
+                    let list = rows.iter_mut().enumerate().fold(
+                        Column::new().spacing(5),
+                        |list, (i, row)| {
+                            // Which coins the preview actually spends
+                            let used = match (spent.contains(&row.coin.outpoint), row.selected) {
+                                (true, true) => "Spent",
+                                (true, false) => "Added",
+                                (false, _) => "",
+                            };
+                            list.push(
+                                Row::new()
+                                    .spacing(10)
+                                    .align_items(Align::Center)
+                                    .push(Checkbox::new(
+                                        row.selected,
+                                        describe_coin(&row.coin),
+                                        move |selected| Message::CoinSelected(i, selected),
+                                    ))
+                                    .push(
+                                        TextInput::new(
+                                            &mut row.label_input,
+                                            "Label",
+                                            &row.label,
+                                            move |label| Message::CoinLabel(i, label),
+                                        )
+                                        .padding(5)
+                                        .width(Length::Units(200)),
+                                    )
+                                    .push(Text::new(used).width(Length::Units(60))),
+                            )
+                        },
+                    );
+                    // Max always spends just the selection
+                    if self.send_all {
+                        Some(list.into())
+                    } else {
+                        Some(
+                            list.push(Checkbox::new(
+                                self.add_more_coins,
+                                "Add more coins if needed",
+                                Message::AddMoreCoins,
+                            ))
+                            .into(),
+                        )
+                    }
+                }
+            }
+        };
+
+        let rates = &self.fee_rates;
+        let fee_speed = self.fee_speed;
+        let speeds = FeeSpeed::all().iter().cloned().fold(
+            Column::new().padding(10).spacing(10),
+            |speeds, speed| {
+                let label = match (preset_rate(rates, speed), rates) {
+                    (Some(rate), _) => format!("{}: {:.1} sat/vB", String::from(speed), rate),
+                    (None, Some(_)) if speed != FeeSpeed::Custom => {
+                        format!("{}: no estimate", String::from(speed))
+                    }
+                    _ => String::from(speed),
+                };
+                speeds.push(Radio::new(
+                    speed,
+                    label,
+                    Some(fee_speed),
+                    Message::FeeSpeedSelected,
+                ))
+            },
+        );
+
+        let custom_fee_rate_input = TextInput::new(
+            &mut self.custom_fee_rate_input,
+            "sat/vB",
+            &self.custom_fee_rate,
+            Message::CustomFeeRate,
+        )
+        .padding(15);
 
-            let amount_input = TextInput::new(
-                &mut self.amount_input,
-                "Amount",
-                &self.amount,
-                Message::Amount,
+        let button: Element<_> = if self.building {
+            Text::new("Preparing").into()
+        } else {
+            Button::new(
+                &mut self.broadcast_button,
+                Text::new("Review").horizontal_alignment(HorizontalAlignment::Center),
             )
-            .padding(15);
+            .on_press(Message::Review)
+            .into()
+        };
 
-            let button: Element<_> = if self.sending {
-                Text::new("Sending").into()
-            } else {
-                // TODO: maybe validate address / amount here?
-                Button::new(
-                    &mut self.broadcast_button,
-                    Text::new("Send").horizontal_alignment(HorizontalAlignment::Center),
-                )
-                .on_press(Message::Send)
-                .into()
+        let mut content = Column::new()
+            .padding(20)
+            .align_items(Align::Center)
+            .spacing(10)
+            .push(recipients)
+            .push(list_buttons)
+            .push(import);
+
+        if let Some(import_message) = self.import_message.clone() {
+            content = content.push(Text::new(import_message));
+        }
+        if let Some(total) = total {
+            content = content.push(Text::new(format!(
+                "Total: {} to {} recipients",
+                config::get().unit.format(total as i64),
+                count
+            )));
+        }
+        content = content.push(coins_button);
+
+        if let Some(coins) = coins {
+            content = content.push(coins);
+        }
+        content = content.push(speeds);
+
+        if self.fee_speed == FeeSpeed::Custom {
+            content = content.push(custom_fee_rate_input);
+        }
+        if let Some(fee) = self.fee {
+            content = content.push(Text::new(format!(
+                "Fee: {}",
+                config::get().unit.format(fee as i64)
+            )));
+        }
+        if let (true, Some(spent)) = (self.show_coins, &self.spent) {
+            let added = spent
+                .iter()
+                .filter(|outpoint| !selected.contains(outpoint))
+                .count();
+            let coins = match added {
+                0 => format!("Spends {} coins", spent.len()),
+                added => format!(
+                    "Spends {} coins, {} added by the wallet",
+                    spent.len(),
+                    added
+                ),
             };
+            content = content.push(Text::new(coins));
+        }
+        if let (true, Some(received)) = (self.send_all, self.received) {
+            content = content.push(Text::new(format!(
+                "Recipient gets: {}",
+                config::get().unit.format(received as i64)
+            )));
+        }
+        if let Some(fee_error) = self.fee_error.clone() {
+            content = content.push(Text::new(fee_error));
+        }
 
+        if let Some(error) = self.error.clone() {
+            content = content.push(Text::new(error));
+        };
+
+        content.push(button).into()
+    }
+}
+
+/// An output of the transaction under review
+#[derive(Debug, Clone)]
+struct Output {
+    address: String,
+    amount: u64,
+    /// The recipient's label, or None for change coming back to this account
+    recipient: Option<String>,
+}
+
+/// The built transaction, shown before anything is signed or broadcast
+#[derive(Debug, Clone)]
+pub struct Review {
+    /// The form as it was, to go back to on Cancel
+    form: AddressAndAmount,
+    psbt: PartiallySignedTransaction,
+    outputs: Vec<Output>,
+    /// Spent outpoints, with their amounts when the PSBT has them
+    inputs: Vec<(OutPoint, Option<u64>)>,
+    fee: u64,
+    fee_rate: f32,
+    password_input: text_input::State,
+    password: String,
+    txid: Option<Txid>,
+    /// Base64 PSBT for other wallets to sign: unsigned from watch-only accounts, partially
+    /// signed from multisig accounts without enough keys here
+    exported: Option<String>,
+    /// Whether the exported PSBT already carries this account's signatures
+    partially_signed: bool,
+    copy_button: button::State,
+    copied: Option<Result<(), String>>,
+    error: Option<String>,
+    sending: bool,
+}
+
+impl<'a> Review {
+    fn new(
+        form: AddressAndAmount,
+        psbt: PartiallySignedTransaction,
+        details: TransactionDetails,
+        fee_rate: f32,
+    ) -> Self {
+        let network = form.account.network;
+        // Same order as the form's recipients, so labels line up
+        let recipients: Vec<(Script, String)> = form
+            .payments()
+            .unwrap_or_default()
+            .into_iter()
+            .zip(form.recipients.iter())
+            .map(|((address, _), recipient)| {
+                (address.script_pubkey(), recipient.label.trim().to_string())
+            })
+            .collect();
+        let tx = &psbt.global.unsigned_tx;
+        let outputs = tx
+            .output
+            .iter()
+            .map(|output| Output {
+                address: match Address::from_script(&output.script_pubkey, network) {
+                    Some(address) => address.to_string(),
+                    None => "unknown script".to_string(),
+                },
+                amount: output.value,
+                recipient: recipients
+                    .iter()
+                    .find(|(script, _)| *script == output.script_pubkey)
+                    .map(|(_, label)| label.clone()),
+            })
+            .collect();
+        let inputs = tx
+            .input
+            .iter()
+            .zip(psbt.inputs.iter())
+            .map(|(input, psbt_input)| {
+                let outpoint = input.previous_output;
+                let amount = match (&psbt_input.witness_utxo, &psbt_input.non_witness_utxo) {
+                    (Some(txout), _) => Some(txout.value),
+                    (None, Some(prev_tx)) => prev_tx
+                        .output
+                        .get(outpoint.vout as usize)
+                        .map(|txout| txout.value),
+                    (None, None) => None,
+                };
+                (outpoint, amount)
+            })
+            .collect();
+        Self {
+            form,
+            psbt,
+            outputs,
+            inputs,
+            fee: details.fees,
+            fee_rate,
+            password_input: text_input::State::new(),
+            password: "".to_string(),
+            txid: None,
+            exported: None,
+            partially_signed: false,
+            copy_button: button::State::new(),
+            copied: None,
+            error: None,
+            sending: false,
+        }
+    }
+    /// Size once signed. The signatures aren't there yet, so this is an estimate.
+    fn vsize(&self) -> Option<u64> {
+        estimated_vsize(&self.psbt.global.unsigned_tx, &self.form.account.descriptor)
+    }
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::Password(password) => {
+                self.password = password;
+                self.error = None;
+                Command::none()
+            }
+            Message::Confirm => {
+                let account = self.form.account.clone();
+                // Watch-only accounts stop at an unsigned PSBT
+                if account.is_watch_only() {
+                    self.exported = Some(base64::encode(&serialize(&self.psbt)));
+                    return Command::none();
+                }
+                self.sending = true;
+                self.error = None;
+                Command::perform(
+                    tasks::sign_and_broadcast(account, self.psbt.clone(), self.password.clone()),
+                    Message::SendResult,
+                )
+            }
+            Message::SendResult(result) => {
+                self.sending = false;
+                match result {
+                    Ok(Signed::Broadcast(txid)) => self.txid = Some(txid),
+                    Ok(Signed::Partially(psbt)) => {
+                        self.exported = Some(base64::encode(&serialize(&psbt)));
+                        self.partially_signed = true;
+                        self.psbt = psbt;
+                    }
+                    Err(Error::WrongPassword) => self.error = Some("Wrong password".to_string()),
+                    Err(Error::CouldNotFinalize) => {
+                        self.error = Some(
+                            "None of this account's keys could sign this transaction".to_string(),
+                        )
+                    }
+                    Err(_) => self.error = Some("Could not sign".to_string()),
+                }
+                Command::none()
+            }
+            Message::CopyPsbt => {
+                if let Some(psbt) = &self.exported {
+                    self.copied = Some(copy_to_clipboard(psbt.clone()));
+                }
+                Command::none()
+            }
+            // Keep the form current for Cancel
+            message => self.form.update(message),
+        }
+    }
+    pub fn view(
+        &'a mut self,
+        next_button: &'a mut button::State,
+        back_button: &'a mut button::State,
+    ) -> Element<'a, Message> {
+        if let Some(txid) = self.txid {
+            return Text::new(txid.to_string()).into();
+        }
+        if let Some(psbt) = self.exported.clone() {
+            let heading = if self.partially_signed {
+                "Signed with this account's keys. Pass the PSBT to the other signers to finish it."
+            } else {
+                "Unsigned PSBT, sign it with another wallet"
+            };
             let mut content = Column::new()
                 .padding(20)
+                .spacing(10)
                 .align_items(Align::Center)
-                .push(address_input)
-                .push(amount_input);
+                .push(Text::new(heading))
+                .push(Text::new(psbt).size(14))
+                .push(
+                    Button::new(&mut self.copy_button, Text::new("Copy"))
+                        .on_press(Message::CopyPsbt),
+                );
+            match &self.copied {
+                Some(Ok(())) => content = content.push(Text::new("Copied")),
+                Some(Err(e)) => content = content.push(Text::new(format!("Couldn't copy: {}", e))),
+                None => {}
+            }
+            return content.into();
+        }
 
-            if let Some(error) = self.error.clone() {
-                content = content.push(Text::new(error));
+        let unit = config::get().unit;
+        let account = &self.form.account;
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Review payment").size(30))
+            .push(Text::new("Recipients"));
+        for output in self.outputs.iter().filter(|o| o.recipient.is_some()) {
+            let mut line = format!("{} · {}", unit.format(output.amount as i64), output.address);
+            if let Some(label) = output.recipient.as_ref().filter(|label| !label.is_empty()) {
+                line.push_str(&format!(" · {}", label));
+            }
+            content = content.push(Text::new(line).size(16));
+        }
+        content = content.push(Text::new("Change"));
+        let change: Vec<&Output> = self
+            .outputs
+            .iter()
+            .filter(|o| o.recipient.is_none())
+            .collect();
+        if change.is_empty() {
+            content = content.push(Text::new("None").size(16));
+        }
+        for output in change {
+            content = content.push(
+                Text::new(format!(
+                    "{} · {}",
+                    unit.format(output.amount as i64),
+                    output.address
+                ))
+                .size(16),
+            );
+        }
+        content = content.push(Text::new("Inputs"));
+        for (outpoint, amount) in &self.inputs {
+            let mut line = match amount {
+                Some(amount) => format!("{} · {}", unit.format(*amount as i64), outpoint),
+                None => outpoint.to_string(),
             };
+            if let Some(label) = account.label(outpoint) {
+                line.push_str(&format!(" · {}", label));
+            }
+            content = content.push(Text::new(line).size(16));
+        }
+        content = content
+            .push(Text::new(format!("Fee: {}", unit.format(self.fee as i64))))
+            .push(Text::new(format!("Fee rate: {:.1} sat/vB", self.fee_rate)));
+        if let Some(vsize) = self.vsize() {
+            content = content.push(Text::new(format!("Virtual size: about {} vB", vsize)));
+        }
 
-            content.push(button).into()
+        if account.is_encrypted() && !account.is_watch_only() {
+            content = content.push(
+                TextInput::new(
+                    &mut self.password_input,
+                    "Password",
+                    &self.password,
+                    Message::Password,
+                )
+                .password()
+                .padding(15),
+            );
+        }
+        if let Some(error) = self.error.clone() {
+            content = content.push(Text::new(error));
         }
+
+        let confirm_label = if account.is_watch_only() {
+            "Create PSBT"
+        } else if self.sending {
+            "Sending"
+        } else {
+            "Confirm"
+        };
+        let mut back = Button::new(
+            back_button,
+            Text::new("Cancel").horizontal_alignment(HorizontalAlignment::Center),
+        );
+        let mut next = Button::new(
+            next_button,
+            Text::new(confirm_label).horizontal_alignment(HorizontalAlignment::Center),
+        );
+        // Nothing to take back once signing started
+        if !self.sending {
+            back = back.on_press(Message::Cancel);
+            next = next.on_press(Message::Confirm);
+        }
+        content
+            .push(Row::new().spacing(20).push(back).push(next))
+            .into()
     }
 }
 
 #[derive(Debug, Clone)]
 enum Step {
     AddressAndAmount(AddressAndAmount),
+    // Boxed, as it holds a whole form besides the transaction
+    Review(Box<Review>),
 }
 
 #[derive(Debug, Clone)]
@@ -165,12 +1180,31 @@ impl<'a> Page {
     }
     pub fn update(&mut self, message: Message) -> Command<Message> {
         match self.step {
-            Step::AddressAndAmount(ref mut step) => step.update(message),
+            Step::AddressAndAmount(ref mut step) => match message {
+                Message::PsbtBuilt(fee_rate, Ok((psbt, details))) => {
+                    let mut form = step.clone();
+                    form.building = false;
+                    self.step = Step::Review(Box::new(Review::new(form, psbt, details, fee_rate)));
+                    Command::none()
+                }
+                message => step.update(message),
+            },
+            Step::Review(ref mut step) => match message {
+                Message::Cancel => {
+                    let form = step.form.clone();
+                    self.step = Step::AddressAndAmount(form);
+                    Command::none()
+                }
+                message => step.update(message),
+            },
         }
     }
     pub fn view(&mut self) -> Element<Message> {
-        match self.step {
+        // Long recipient lists don't fit the window
+        let content = match self.step {
             Step::AddressAndAmount(ref mut step) => step.view(),
-        }
+            Step::Review(ref mut step) => step.view(&mut self.next_button, &mut self.back_button),
+        };
+        Scrollable::new(&mut self.scroll).push(content).into()
     }
 }
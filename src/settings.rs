@@ -0,0 +1,446 @@
+use iced::{
+    button, scrollable, text_input, Align, Button, Column, Command, Element, HorizontalAlignment,
+    Length, Radio, Row, Scrollable, Text, TextInput,
+};
+
+use crate::config::{self, Config};
+use crate::data::{Account, Theme, Unit};
+use crate::tasks;
+use crate::utils::fingerprint;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    AccountView,
+    GlobalView,
+
+    // Account settings
+    Name(String),
+    WatchedAddresses(String),
+    // Intercepted in main to update the account registry
+    SaveAccount(Account),
+    EncryptPassword(String),
+    EncryptConfirm(String),
+    EncryptAccount,
+    // Intercepted in main to update the account registry
+    AccountEncrypted(Account),
+
+    // Global settings
+    ServerUrl(String),
+    CertificateFingerprint(String),
+    Proxy(String),
+    UnitSelected(Unit),
+    ThemeSelected(Theme),
+    SaveGlobal,
+
+    AccountUpdated(Account),
+}
+
+fn button<'a>(state: &'a mut button::State, label: &str) -> Button<'a, Message> {
+    Button::new(
+        state,
+        Text::new(label).horizontal_alignment(HorizontalAlignment::Center),
+    )
+    .padding(12)
+    .min_width(100)
+}
+
+fn optional(value: &str) -> Option<String> {
+    if value.trim().is_empty() {
+        None
+    } else {
+        Some(value.trim().to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountSettings {
+    name_input: text_input::State,
+    name: String,
+    watched_addresses_input: text_input::State,
+    watched_addresses: String,
+    save_button: button::State,
+    error: Option<String>,
+    // Only for accounts from before encryption, whose private keys are still in the clear
+    password_input: text_input::State,
+    password: String,
+    confirm_input: text_input::State,
+    confirm: String,
+    encrypt_button: button::State,
+    encrypt_error: Option<String>,
+    encrypting: bool,
+}
+
+impl AccountSettings {
+    fn new(account: &Account) -> Self {
+        Self {
+            name_input: text_input::State::new(),
+            name: account.name.clone(),
+            watched_addresses_input: text_input::State::new(),
+            watched_addresses: account.watched_addresses.to_string(),
+            save_button: button::State::new(),
+            error: None,
+            password_input: text_input::State::new(),
+            password: "".to_string(),
+            confirm_input: text_input::State::new(),
+            confirm: "".to_string(),
+            encrypt_button: button::State::new(),
+            encrypt_error: None,
+            encrypting: false,
+        }
+    }
+    fn edited(&self, account: &Account) -> Result<Account, String> {
+        let watched_addresses = match self.watched_addresses.parse::<u32>() {
+            Ok(watched_addresses) if watched_addresses > 0 => watched_addresses,
+            _ => return Err("Addresses to watch must be a positive number".to_string()),
+        };
+        if self.name.trim().is_empty() {
+            return Err("Name can't be empty".to_string());
+        }
+        let mut account = account.clone();
+        account.name = self.name.trim().to_string();
+        account.watched_addresses = watched_addresses;
+        Ok(account)
+    }
+    fn view(&mut self, account: &Account) -> Element<Message> {
+        // The save button emits the edited account directly, so validate up front
+        let edited = self.edited(account).ok();
+
+        let name =
+            TextInput::new(&mut self.name_input, "Name", &self.name, Message::Name).padding(15);
+        let watched_addresses = TextInput::new(
+            &mut self.watched_addresses_input,
+            "Addresses to watch",
+            &self.watched_addresses,
+            Message::WatchedAddresses,
+        )
+        .padding(15);
+        let fingerprint = fingerprint(&account.descriptor)
+            .map(|fingerprint| fingerprint.to_string())
+            .unwrap_or_else(|| "Unknown".to_string());
+
+        let mut content = Column::new()
+            .spacing(10)
+            .push(Text::new("Name"))
+            .push(name)
+            .push(Text::new(format!("Network: {}", account.network)))
+            .push(Text::new(format!("Fingerprint: {}", fingerprint)))
+            .push(Text::new("Descriptor"))
+            .push(Text::new(account.descriptor.clone()).size(16));
+        if let Some(change_descriptor) = &account.change_descriptor {
+            content = content
+                .push(Text::new("Change descriptor"))
+                .push(Text::new(change_descriptor.clone()).size(16));
+        }
+        content = content
+            .push(Text::new("Addresses to watch"))
+            .push(watched_addresses);
+        if let Some(error) = self.error.clone() {
+            content = content.push(Text::new(error));
+        }
+        let save = button(&mut self.save_button, "Save");
+        let save = match edited {
+            Some(edited) => save.on_press(Message::SaveAccount(edited)),
+            None => save,
+        };
+        content = content.push(save);
+
+        if !account.is_encrypted() && !account.is_watch_only() {
+            content = content
+                .push(Text::new(
+                    "This account's private keys are stored unencrypted. Set a password to \
+                     encrypt them.",
+                ))
+                .push(
+                    TextInput::new(
+                        &mut self.password_input,
+                        "Password",
+                        &self.password,
+                        Message::EncryptPassword,
+                    )
+                    .password()
+                    .padding(15),
+                )
+                .push(
+                    TextInput::new(
+                        &mut self.confirm_input,
+                        "Confirm password",
+                        &self.confirm,
+                        Message::EncryptConfirm,
+                    )
+                    .password()
+                    .padding(15),
+                );
+            if let Some(error) = self.encrypt_error.clone() {
+                content = content.push(Text::new(error));
+            }
+            content = if self.encrypting {
+                content.push(Text::new("Encrypting..."))
+            } else {
+                content.push(
+                    button(&mut self.encrypt_button, "Encrypt").on_press(Message::EncryptAccount),
+                )
+            };
+        }
+        content.into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobalSettings {
+    // Server settings are shown for the current account's network
+    url_input: text_input::State,
+    url: String,
+    certificate_fingerprint_input: text_input::State,
+    certificate_fingerprint: String,
+    proxy_input: text_input::State,
+    proxy: String,
+    unit: Unit,
+    theme: Theme,
+    save_button: button::State,
+    saved: Option<Result<(), String>>,
+}
+
+impl GlobalSettings {
+    fn new(account: &Account) -> Self {
+        let config = config::get();
+        let server = config.servers.get(account.network);
+        Self {
+            url_input: text_input::State::new(),
+            url: server.url.clone(),
+            certificate_fingerprint_input: text_input::State::new(),
+            certificate_fingerprint: server.certificate_fingerprint.clone().unwrap_or_default(),
+            proxy_input: text_input::State::new(),
+            proxy: server.proxy.clone().unwrap_or_default(),
+            unit: config.unit,
+            theme: config.theme,
+            save_button: button::State::new(),
+            saved: None,
+        }
+    }
+    fn edited(&self, account: &Account) -> Config {
+        let mut config = config::get();
+        let server = config.servers.get_mut(account.network);
+        server.url = self.url.trim().to_string();
+        server.certificate_fingerprint = optional(&self.certificate_fingerprint);
+        server.proxy = optional(&self.proxy);
+        config.unit = self.unit;
+        config.theme = self.theme;
+        config
+    }
+    fn view(&mut self, account: &Account) -> Element<Message> {
+        let url = TextInput::new(
+            &mut self.url_input,
+            "tcp://host:port or ssl://host:port",
+            &self.url,
+            Message::ServerUrl,
+        )
+        .padding(15);
+        let certificate_fingerprint = TextInput::new(
+            &mut self.certificate_fingerprint_input,
+            "SHA256 of the server certificate (optional)",
+            &self.certificate_fingerprint,
+            Message::CertificateFingerprint,
+        )
+        .padding(15);
+        let proxy = TextInput::new(
+            &mut self.proxy_input,
+            "SOCKS5 proxy host:port (optional)",
+            &self.proxy,
+            Message::Proxy,
+        )
+        .padding(15);
+
+        let unit = self.unit;
+        let units = Unit::all()
+            .iter()
+            .cloned()
+            .fold(Row::new().spacing(20), |row, choice| {
+                row.push(Radio::new(
+                    choice,
+                    choice,
+                    Some(unit),
+                    Message::UnitSelected,
+                ))
+            });
+        let theme = self.theme;
+        let themes = Theme::all()
+            .iter()
+            .cloned()
+            .fold(Row::new().spacing(20), |row, choice| {
+                row.push(Radio::new(
+                    choice,
+                    choice,
+                    Some(theme),
+                    Message::ThemeSelected,
+                ))
+            });
+
+        let mut content = Column::new()
+            .spacing(10)
+            .push(Text::new(format!("{} server", account.network)))
+            .push(url)
+            .push(certificate_fingerprint)
+            .push(proxy)
+            .push(Text::new("Display units"))
+            .push(units)
+            .push(Text::new("Theme"))
+            .push(themes);
+        match &self.saved {
+            Some(Ok(())) => content = content.push(Text::new("Saved")),
+            Some(Err(error)) => content = content.push(Text::new(error.clone())),
+            None => {}
+        }
+        content
+            .push(button(&mut self.save_button, "Save").on_press(Message::SaveGlobal))
+            .into()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Account(AccountSettings),
+    Global(GlobalSettings),
+}
+
+#[derive(Debug, Clone)]
+pub struct Page {
+    scroll: scrollable::State,
+    account_view_button: button::State,
+    global_view_button: button::State,
+    step: Step,
+    account: Account,
+}
+
+impl<'a> Page {
+    pub fn new(account: Account) -> Self {
+        Self {
+            scroll: scrollable::State::new(),
+            account_view_button: button::State::new(),
+            global_view_button: button::State::new(),
+            step: Step::Account(AccountSettings::new(&account)),
+            account,
+        }
+    }
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::AccountView => self.step = Step::Account(AccountSettings::new(&self.account)),
+            Message::GlobalView => self.step = Step::Global(GlobalSettings::new(&self.account)),
+            Message::Name(name) => {
+                if let Step::Account(ref mut step) = self.step {
+                    step.name = name;
+                    step.error = step.edited(&self.account).err();
+                }
+            }
+            Message::WatchedAddresses(watched_addresses) => {
+                if let Step::Account(ref mut step) = self.step {
+                    step.watched_addresses = watched_addresses;
+                    step.error = step.edited(&self.account).err();
+                }
+            }
+            // Main has already saved it by the time it gets here
+            Message::SaveAccount(account) => self.account = account,
+            Message::EncryptPassword(password) => {
+                if let Step::Account(ref mut step) = self.step {
+                    step.password = password;
+                    step.encrypt_error = None;
+                }
+            }
+            Message::EncryptConfirm(confirm) => {
+                if let Step::Account(ref mut step) = self.step {
+                    step.confirm = confirm;
+                    step.encrypt_error = None;
+                }
+            }
+            Message::EncryptAccount => {
+                if let Step::Account(ref mut step) = self.step {
+                    if step.encrypting {
+                        return Command::none();
+                    }
+                    if step.password.is_empty() {
+                        step.encrypt_error = Some("Password can't be empty".to_string());
+                    } else if step.password != step.confirm {
+                        step.encrypt_error = Some("Passwords don't match".to_string());
+                    } else {
+                        // Deriving the encryption key is slow, so do it off the UI thread
+                        step.encrypting = true;
+                        return Command::perform(
+                            tasks::encrypt_account(
+                                self.account.clone(),
+                                self.account.descriptor.clone(),
+                                self.account.change_descriptor.clone(),
+                                step.password.clone(),
+                            ),
+                            Message::AccountEncrypted,
+                        );
+                    }
+                }
+            }
+            // Main has already saved it by the time it gets here
+            Message::AccountEncrypted(account) => {
+                self.step = Step::Account(AccountSettings::new(&account));
+                self.account = account;
+            }
+            Message::ServerUrl(url) => {
+                if let Step::Global(ref mut step) = self.step {
+                    step.url = url;
+                    step.saved = None;
+                }
+            }
+            Message::CertificateFingerprint(certificate_fingerprint) => {
+                if let Step::Global(ref mut step) = self.step {
+                    step.certificate_fingerprint = certificate_fingerprint;
+                    step.saved = None;
+                }
+            }
+            Message::Proxy(proxy) => {
+                if let Step::Global(ref mut step) = self.step {
+                    step.proxy = proxy;
+                    step.saved = None;
+                }
+            }
+            Message::UnitSelected(unit) => {
+                if let Step::Global(ref mut step) = self.step {
+                    step.unit = unit;
+                    step.saved = None;
+                }
+            }
+            Message::ThemeSelected(theme) => {
+                if let Step::Global(ref mut step) = self.step {
+                    step.theme = theme;
+                    step.saved = None;
+                }
+            }
+            Message::SaveGlobal => {
+                if let Step::Global(ref mut step) = self.step {
+                    let result = config::set(step.edited(&self.account));
+                    step.saved = Some(result.map_err(|e| format!("Couldn't save: {:?}", e)));
+                }
+            }
+            Message::AccountUpdated(account) => self.account = account,
+        };
+        Command::none()
+    }
+    pub fn view(&mut self) -> Element<Message> {
+        let tabs = Row::new()
+            .spacing(10)
+            .push(button(&mut self.account_view_button, "Account").on_press(Message::AccountView))
+            .push(button(&mut self.global_view_button, "Global").on_press(Message::GlobalView));
+
+        let content = match self.step {
+            Step::Account(ref mut step) => step.view(&self.account),
+            Step::Global(ref mut step) => step.view(&self.account),
+        };
+
+        let content = Column::new()
+            .padding(20)
+            .spacing(20)
+            .align_items(Align::Center)
+            .push(tabs)
+            .push(content);
+
+        Scrollable::new(&mut self.scroll)
+            .push(content)
+            .width(Length::Fill)
+            .into()
+    }
+}
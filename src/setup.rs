@@ -1,13 +1,25 @@
 use iced::{
-    button, scrollable, text_input, Align, Button, Column, Command, Container, Element,
+    button, scrollable, text_input, Align, Button, Checkbox, Column, Command, Container, Element,
     HorizontalAlignment, Length, Radio, Row, Scrollable, Text, TextInput,
 };
 
-use crate::data::{Account, Entropy};
-use crate::utils::{generate_entropy, mnemonic};
+use crate::backup;
+use crate::data::{Account, Entropy, EntropySource, Language, Method, ScriptType, Signer};
+use crate::error::Error;
+use crate::shamir::{self, MAX_SHARES};
+use crate::tasks;
+use crate::utils::{
+    account_path, change_descriptor, check_network, entropy, entropy_from_rolls, generate_entropy,
+    mnemonic, multisig_path, normalize, parse_xpub, roll_bits, rolls, seed, split_key_origin,
+    suggestions, watch_only_descriptor,
+};
 
-use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey};
 use bitcoin::Network;
+use log::warn;
+use rand::seq::index::sample;
+use rand::thread_rng;
+use std::path::PathBuf;
 
 fn button<'a, Message>(state: &'a mut button::State, label: &str) -> Button<'a, Message> {
     Button::new(
@@ -25,7 +37,43 @@ pub enum Message {
     SetupComplete(Account),
     NetworkSelected(Network),
     Name(String),
+    MethodSelected(Method),
+    LanguageSelected(Language),
     HowManyWords(Entropy),
+    EntropySourceSelected(EntropySource),
+    Rolls(String),
+    RollsOnly(bool),
+    // Shamir backups
+    SplitShares(bool),
+    GroupThreshold(String),
+    MemberThreshold(usize, String),
+    MemberCount(usize, String),
+    AddGroup,
+    RemoveGroup(usize),
+    Share(usize, String),
+    AddShare,
+    Phrase(String),
+    Suggestion(String),
+    VerifyWord(usize, String),
+    Import(String),
+    SkipVerification,
+    Passphrase(String),
+    ConfirmPassphrase(String),
+    SheetPath(String),
+    SaveSheet,
+    SheetSaved(Result<PathBuf, Error>),
+    Password(String),
+    ConfirmPassword(String),
+    // Multisig
+    AddSigner(Method),
+    AddAccountSigner,
+    RemoveSigner(usize),
+    Threshold(String),
+    AccountIndex(String),
+    ScriptTypeSelected(ScriptType),
+    SignerAccountSelected(usize),
+    SignerPassword(String),
+    AccountSignerLoaded(Result<Signer, Error>),
 }
 
 #[derive(Debug, Clone)]
@@ -33,139 +81,1182 @@ pub struct Page {
     scroll: scrollable::State,
     next_button: button::State,
     back_button: button::State,
+    draft: Draft,
     step: Step,
+    /// Existing accounts, to reuse their keys as multisig signers
+    accounts: Vec<Account>,
+}
+
+/// Everything chosen so far. Steps only hold their own widget state, so going back and forth
+/// never loses a choice.
+#[derive(Debug, Clone, Default)]
+struct Draft {
+    network: Option<Network>,
+    name: String,
+    method: Option<Method>,
+    /// Word list for generated and restored seeds
+    language: Language,
+    how_many: Option<Entropy>,
+    entropy_source: EntropySource,
+    /// Dice rolls or coin flips, as typed
+    rolls: String,
+    /// Skip mixing in system randomness. Only as good as the user's dice.
+    rolls_only: bool,
+    words: Vec<String>,
+    /// Generated words can't be used until the user proves they wrote them down
+    backup_verified: bool,
+    /// Also back up generated words as SLIP-39 shares
+    split: bool,
+    /// How many Shamir groups recover the seed, as typed
+    group_threshold: String,
+    /// Each Shamir group's (shares needed, shares made), as typed
+    groups: Vec<(String, String)>,
+    /// The shares last made, group by group
+    shares: Vec<Vec<String>>,
+    /// Optional BIP39 passphrase, a.k.a. the "25th word"
+    passphrase: String,
+    /// Only for single-key accounts, multisig is always native segwit
+    script_type: ScriptType,
+    /// Account number in the derivation path, as typed. Blank means the first account.
+    account_index: String,
+    /// Where to save the backup sheet, as typed
+    sheet_path: String,
+    /// Multisig keys collected so far
+    signers: Vec<Signer>,
+    /// How many signers must sign, as typed
+    threshold: String,
+    /// How the multisig signer currently being entered is sourced
+    adding: Option<Method>,
+}
+
+impl Draft {
+    fn is_multisig(&self) -> bool {
+        self.method == Some(Method::Multisig)
+    }
+    /// Where the key being entered comes from, for single and multisig accounts alike
+    fn source(&self) -> Option<Method> {
+        if self.is_multisig() {
+            self.adding
+        } else {
+            self.method
+        }
+    }
+    fn account_index(&self) -> Option<u32> {
+        let index = self.account_index.trim();
+        if index.is_empty() {
+            return Some(0);
+        }
+        // Hardened derivation only goes up to 2^31
+        index.parse().ok().filter(|&index| index < 1 << 31)
+    }
+    /// Where the key being derived from the seed will sit
+    fn derivation_path(&self, account_index: u32) -> DerivationPath {
+        let network = self.network.expect("network was chosen");
+        if self.is_multisig() {
+            multisig_path(network, account_index)
+        } else {
+            account_path(self.script_type.purpose(), network, account_index)
+        }
+    }
+    /// Private signers must be encrypted, public-only multisig accounts are watch-only
+    fn has_private_signer(&self) -> bool {
+        self.signers.iter().any(|signer| signer.xprv.is_some())
+    }
 }
 
 #[derive(Debug, Clone)]
 enum Step {
-    Network {
-        network: Option<Network>,
-    },
+    Network,
     Name {
         input_state: text_input::State,
-        network: Network,
-        name: String,
+        error: Option<String>,
+    },
+    Method,
+    Language,
+    HowManyWords,
+    Rolls {
+        input_state: text_input::State,
+        error: Option<String>,
     },
-    HowManyWords {
-        network: Network,
-        name: String,
-        how_many: Option<Entropy>,
+    DisplayWords,
+    ShamirGroups {
+        threshold_input: text_input::State,
+        inputs: Vec<(text_input::State, text_input::State)>,
+        add_button: button::State,
+        remove_buttons: Vec<button::State>,
+        error: Option<String>,
     },
-    DisplayWords {
-        network: Network,
-        name: String,
-        how_many: Entropy,
-        words: Vec<String>,
-        descriptor: String,
+    ShamirShares,
+    /// Enter shares, to prove a new Shamir backup works or to restore from one
+    Shares {
+        inputs: Vec<text_input::State>,
+        shares: Vec<String>,
+        add_button: button::State,
+        error: Option<String>,
+    },
+    Verify {
+        // Zero-based word positions being asked for
+        positions: Vec<usize>,
+        inputs: Vec<text_input::State>,
+        answers: Vec<String>,
+        skip_button: button::State,
+        error: Option<String>,
+    },
+    Restore {
+        phrase_input: text_input::State,
+        phrase: String,
+        suggestion_buttons: Vec<button::State>,
+        error: Option<String>,
+    },
+    Import {
+        input_state: text_input::State,
+        text: String,
+        error: Option<String>,
+    },
+    Passphrase {
+        passphrase_input: text_input::State,
+        confirm_input: text_input::State,
+        confirm: String,
+        error: Option<String>,
+    },
+    ScriptType,
+    Derivation {
+        index_input: text_input::State,
+        error: Option<String>,
+    },
+    BackupSheet {
+        sheet: Option<String>,
+        path_input: text_input::State,
+        save_button: button::State,
+        saved: Option<PathBuf>,
+        error: Option<String>,
+        saving: bool,
+    },
+    Password {
+        password_input: text_input::State,
+        password: String,
+        confirm_input: text_input::State,
+        confirm: String,
+        error: Option<String>,
+        encrypting: bool,
+    },
+    Signers {
+        generate_button: button::State,
+        restore_button: button::State,
+        import_button: button::State,
+        account_button: button::State,
+        remove_buttons: Vec<button::State>,
+        threshold_input: text_input::State,
+        error: Option<String>,
+    },
+    AccountSigner {
+        selected: Option<usize>,
+        password_input: text_input::State,
+        password: String,
+        error: Option<String>,
+        decrypting: bool,
     },
-    //CreateAccount {
-    //name: String,
-    //how_many: u8,
-    //words: Vec<String>,
-    //account: Account,
-    //},
 }
 
-fn generate(entropy: Entropy, network: Network) -> (Vec<String>, String) {
+const SUGGESTIONS: usize = 6;
+
+/// How many words the backup quiz asks for
+const QUIZ_WORDS: usize = 4;
+
+impl Step {
+    fn verify(words: &[String]) -> Self {
+        let mut positions = sample(&mut thread_rng(), words.len(), QUIZ_WORDS).into_vec();
+        positions.sort();
+        Step::Verify {
+            positions,
+            inputs: vec![text_input::State::new(); QUIZ_WORDS],
+            answers: vec!["".to_string(); QUIZ_WORDS],
+            skip_button: button::State::new(),
+            error: None,
+        }
+    }
+    fn shamir_groups(groups: usize) -> Self {
+        Step::ShamirGroups {
+            threshold_input: text_input::State::new(),
+            inputs: vec![(text_input::State::new(), text_input::State::new()); groups],
+            add_button: button::State::new(),
+            remove_buttons: vec![button::State::new(); groups],
+            error: None,
+        }
+    }
+    fn shares() -> Self {
+        Step::Shares {
+            inputs: vec![text_input::State::new(); 2],
+            shares: vec!["".to_string(); 2],
+            add_button: button::State::new(),
+            error: None,
+        }
+    }
+    fn restore(words: &[String]) -> Self {
+        Step::Restore {
+            phrase_input: text_input::State::new(),
+            phrase: words.join(" "),
+            suggestion_buttons: vec![button::State::new(); SUGGESTIONS],
+            error: None,
+        }
+    }
+    fn passphrase() -> Self {
+        Step::Passphrase {
+            passphrase_input: text_input::State::new(),
+            confirm_input: text_input::State::new(),
+            confirm: "".to_string(),
+            error: None,
+        }
+    }
+    fn derivation() -> Self {
+        Step::Derivation {
+            index_input: text_input::State::new(),
+            error: None,
+        }
+    }
+    fn password() -> Self {
+        Step::Password {
+            password_input: text_input::State::new(),
+            password: "".to_string(),
+            confirm_input: text_input::State::new(),
+            confirm: "".to_string(),
+            error: None,
+            encrypting: false,
+        }
+    }
+    fn name() -> Self {
+        Step::Name {
+            input_state: text_input::State::new(),
+            error: None,
+        }
+    }
+    fn rolls() -> Self {
+        Step::Rolls {
+            input_state: text_input::State::new(),
+            error: None,
+        }
+    }
+    fn import() -> Self {
+        Step::Import {
+            input_state: text_input::State::new(),
+            text: "".to_string(),
+            error: None,
+        }
+    }
+    fn signers(signers: &[Signer]) -> Self {
+        Step::Signers {
+            generate_button: button::State::new(),
+            restore_button: button::State::new(),
+            import_button: button::State::new(),
+            account_button: button::State::new(),
+            remove_buttons: vec![button::State::new(); signers.len()],
+            threshold_input: text_input::State::new(),
+            error: None,
+        }
+    }
+    fn account_signer() -> Self {
+        Step::AccountSigner {
+            selected: None,
+            password_input: text_input::State::new(),
+            password: "".to_string(),
+            error: None,
+            decrypting: false,
+        }
+    }
+}
+
+fn generate(entropy: Entropy, language: Language) -> Vec<String> {
     let data = generate_entropy(entropy);
-    let words = mnemonic(&data).expect("couldn't create mnemonic");
-    let xprv = ExtendedPrivKey::new_master(network, &data).unwrap();
-    let descriptor = format!("wpkh({}/0/*)", xprv);
-    // TODO: don't return a "descriptor, instead return a vec of "signers". In multisig we can
-    // combine these signers into descriptor pair once we have enough. Signers are like an
-    // intermediate state in the setup wizard, and help to identify keys / devices shared between
-    // multiple accounts
-    (words, descriptor)
+    mnemonic(&data, language).expect("couldn't create mnemonic")
+}
+
+fn master_key(words: &[String], passphrase: &str, network: Network) -> ExtendedPrivKey {
+    ExtendedPrivKey::new_master(network, &seed(words, passphrase)).unwrap()
+}
+
+/// Decode the typed SLIP-39 shares, ignoring blank ones, and recover the seed entropy
+fn recover_shares(shares: &[String]) -> Result<Vec<u8>, String> {
+    let decoded = shares
+        .iter()
+        .enumerate()
+        .filter(|(_, share)| !share.trim().is_empty())
+        .map(|(number, share)| {
+            shamir::decode(share).map_err(|e| match e {
+                Error::InvalidWord(position) => format!(
+                    "Share {}: word {} isn't in the word list",
+                    number + 1,
+                    position + 1
+                ),
+                Error::InvalidChecksum => format!(
+                    "Share {}: checksum doesn't match. Check the words and their order.",
+                    number + 1
+                ),
+                Error::Mnemonic(message) => format!("Share {}: {}", number + 1, message),
+                e => format!("{:?}", e),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // The BIP39 passphrase step protects the seed, so the shares carry none of their own
+    shamir::combine(&decoded, "").map_err(|e| match e {
+        Error::Shamir(message) => message,
+        e => format!("{:?}", e),
+    })
+}
+
+/// Documents folder, or home, with a file name from the account name
+fn default_sheet_path(name: &str) -> String {
+    let mut file: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    if file.is_empty() {
+        file = "wallet".to_string();
+    }
+    dirs::document_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(format!("{}-backup.svg", file))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Receive and change descriptors for a single-key account, with key origin so other wallets
+/// can find the same addresses
+fn descriptors(
+    master: &ExtendedPrivKey,
+    path: DerivationPath,
+    script_type: ScriptType,
+) -> (String, String) {
+    let signer = Signer::from_master(master, path, String::new()).expect("derivable path");
+    (
+        script_type.descriptor(&format!("{}/0/*", signer.key())),
+        script_type.descriptor(&format!("{}/1/*", signer.key())),
+    )
+}
+
+/// `threshold`-of-n descriptor over `chain` (0 for receive, 1 for change) of every signer.
+/// Sorting the keys means signer order doesn't matter when another wallet recreates it.
+fn multisig_descriptor(threshold: usize, signers: &[Signer], chain: u32) -> String {
+    let keys: Vec<String> = signers
+        .iter()
+        .map(|signer| format!("{}/{}/*", signer.key(), chain))
+        .collect();
+    format!("wsh(sortedmulti({},{}))", threshold, keys.join(","))
 }
 
 impl<'a> Page {
-    pub fn new() -> Self {
+    pub fn new(accounts: Vec<Account>) -> Self {
         Self {
             next_button: button::State::new(),
             back_button: button::State::new(),
             scroll: scrollable::State::new(),
-            step: Step::Network { network: None },
+            draft: Draft::default(),
+            step: Step::Network,
+            accounts,
         }
     }
-    pub fn update(&mut self, message: Message) -> Command<Message> {
-        match message {
-            Message::Next => match self.step.clone() {
-                Step::Network { network } => {
-                    if let Some(network) = network {
-                        self.step = Step::Name { input_state: text_input::State::new(), name: "".to_string(), network };
+    /// Add a multisig signer and go back to the list. Fails if the key is already in.
+    fn add_signer(&mut self, signer: Signer) -> Result<(), String> {
+        if let Some(existing) = self
+            .draft
+            .signers
+            .iter()
+            .find(|existing| existing.fingerprint == signer.fingerprint)
+        {
+            return Err(format!(
+                "Key {} is already a signer ({})",
+                signer.fingerprint, existing.label
+            ));
+        }
+        self.draft.signers.push(signer);
+        self.draft.words.clear();
+        self.draft.passphrase.clear();
+        self.draft.adding = None;
+        self.step = Step::signers(&self.draft.signers);
+        Ok(())
+    }
+    /// Backup sheet of the account as chosen so far. Multisig sheets leave out seed words, which
+    /// belong to the individual signers.
+    fn sheet_step(&self) -> Step {
+        let network = self.draft.network.expect("network was chosen");
+        let sheet = if self.draft.is_multisig() {
+            // Already validated on the signers step
+            let threshold = self.draft.threshold.trim().parse().unwrap_or(1);
+            backup::sheet(
+                &self.draft.name,
+                network,
+                &[],
+                false,
+                &multisig_descriptor(threshold, &self.draft.signers, 0),
+                Some(&multisig_descriptor(threshold, &self.draft.signers, 1)),
+            )
+        } else {
+            let master = master_key(&self.draft.words, &self.draft.passphrase, network);
+            // Already validated on the derivation step
+            let index = self.draft.account_index().unwrap_or(0);
+            let (descriptor, change_descriptor) = descriptors(
+                &master,
+                self.draft.derivation_path(index),
+                self.draft.script_type,
+            );
+            backup::sheet(
+                &self.draft.name,
+                network,
+                &self.draft.words,
+                !self.draft.passphrase.is_empty(),
+                &descriptor,
+                Some(&change_descriptor),
+            )
+        };
+        Step::BackupSheet {
+            error: sheet.as_ref().err().map(|e| format!("{:?}", e)),
+            sheet: sheet.ok(),
+            path_input: text_input::State::new(),
+            save_button: button::State::new(),
+            saved: None,
+            saving: false,
+        }
+    }
+    /// Step for entering a key from `source`
+    fn key_step(source: Method) -> Step {
+        match source {
+            Method::Generate | Method::Restore | Method::Shamir => Step::Language,
+            Method::WatchOnly => Step::import(),
+            Method::Multisig => unreachable!("multisig isn't a key source"),
+        }
+    }
+    fn next(&mut self) -> Command<Message> {
+        match self.step {
+            Step::Network => {
+                if self.draft.network.is_some() {
+                    self.step = Step::name();
+                }
+            }
+            Step::Name { ref mut error, .. } => {
+                if self.draft.name.trim().is_empty() {
+                    *error = Some("Name can't be empty".to_string());
+                } else {
+                    self.step = Step::Method;
+                }
+            }
+            Step::Method => match self.draft.method {
+                Some(Method::Multisig) => self.step = Step::signers(&self.draft.signers),
+                Some(method) => self.step = Self::key_step(method),
+                None => {}
+            },
+            Step::Import { ref text, .. } if self.draft.is_multisig() => {
+                let result = split_key_origin(text)
+                    .and_then(|(origin, key)| Ok((origin, parse_xpub(key)?.0)))
+                    .map_err(|e| match e {
+                        Error::Import(message) => message,
+                        e => format!("{:?}", e),
+                    })
+                    .and_then(|(origin, xpub)| {
+                        let network = self.draft.network.expect("network was chosen");
+                        check_network(&xpub.to_string(), network).map_err(|e| match e {
+                            Error::Import(message) => message,
+                            e => format!("{:?}", e),
+                        })?;
+                        self.add_signer(Signer::from_xpub(
+                            xpub,
+                            origin,
+                            "Imported xpub".to_string(),
+                        ))
+                    });
+                if let (Err(message), Step::Import { ref mut error, .. }) = (result, &mut self.step)
+                {
+                    *error = Some(message);
+                }
+            }
+            Step::Import {
+                ref text,
+                ref mut error,
+                ..
+            } => {
+                let network = self.draft.network.expect("network was chosen");
+                let imported = watch_only_descriptor(text).and_then(|descriptor| {
+                    check_network(&descriptor, network)?;
+                    Ok(descriptor)
+                });
+                match imported {
+                    // Nothing secret to encrypt, so we're done
+                    Ok(descriptor) => {
+                        let change = change_descriptor(&descriptor);
+                        let account =
+                            Account::new(self.draft.name.clone(), network, descriptor, change);
+                        return Command::perform(async move { account }, Message::SetupComplete);
                     }
-                },
-                Step::Name { network, name, .. } => self.step = Step::HowManyWords { network, name, how_many: None },
-                Step::HowManyWords { network, name, how_many } => {
-                    if let Some(how_many) = how_many {
-                        let (words, descriptor) = generate(how_many, network);
-                        self.step = Step::DisplayWords {
-                            network,
-                            name,
-                            how_many,
-                            words,
-                            descriptor,
+                    Err(Error::Import(message)) => *error = Some(message),
+                    Err(e) => *error = Some(format!("{:?}", e)),
+                }
+            }
+            Step::Signers { ref mut error, .. } => {
+                let count = self.draft.signers.len();
+                match self.draft.threshold.trim().parse::<usize>() {
+                    _ if count < 2 => *error = Some("Add at least two signers".to_string()),
+                    Ok(threshold) if threshold >= 1 && threshold <= count => {
+                        if self.draft.sheet_path.is_empty() {
+                            self.draft.sheet_path = default_sheet_path(&self.draft.name);
+                        }
+                        self.step = self.sheet_step();
+                    }
+                    _ => {
+                        *error = Some(format!(
+                            "Required signatures must be between 1 and {}",
+                            count
+                        ))
+                    }
+                }
+            }
+            Step::AccountSigner {
+                selected,
+                ref password,
+                ref mut error,
+                ref mut decrypting,
+                ..
+            } => {
+                if *decrypting {
+                    return Command::none();
+                }
+                let account = match selected {
+                    Some(index) => self.accounts[index].clone(),
+                    None => {
+                        *error = Some("Choose an account".to_string());
+                        return Command::none();
+                    }
+                };
+                if account.is_encrypted() {
+                    // Decrypting is slow, so do it off the UI thread
+                    *decrypting = true;
+                    return Command::perform(
+                        tasks::account_signer(account, password.clone()),
+                        Message::AccountSignerLoaded,
+                    );
+                }
+                match Signer::from_descriptor(&account.descriptor, account.name.clone()) {
+                    Ok(signer) => {
+                        if let Err(message) = self.add_signer(signer) {
+                            if let Step::AccountSigner { ref mut error, .. } = self.step {
+                                *error = Some(message);
+                            }
                         }
                     }
+                    Err(Error::Import(message)) => *error = Some(message),
+                    Err(e) => *error = Some(format!("{:?}", e)),
                 }
-                Step::DisplayWords {
-                    ..
-                    //name,
-                    //how_many,
-                    //words,
-                    // TODO: intercept this in main
-                } => println!("Setup Done"),
+            }
+            Step::Language => match self.draft.source() {
+                Some(Method::Restore) => self.step = Step::restore(&self.draft.words),
+                Some(Method::Shamir) => self.step = Step::shares(),
+                _ => self.step = Step::HowManyWords,
             },
-            Message::Back => match self.step.clone() {
-                Step::Network { .. } => println!("Can't go back from step 1"),
-                Step::Name { network, .. } => {
-                    self.step = Step::Network {
-                        network: Some(network),
+            Step::HowManyWords => match (self.draft.how_many, self.draft.entropy_source) {
+                (Some(how_many), EntropySource::System) => {
+                    self.draft.words = generate(how_many, self.draft.language);
+                    self.draft.backup_verified = false;
+                    self.step = Step::DisplayWords;
+                }
+                (Some(_), _) => self.step = Step::rolls(),
+                (None, _) => {}
+            },
+            Step::Rolls { ref mut error, .. } => {
+                let how_many = self.draft.how_many.expect("words were chosen");
+                let source = self.draft.entropy_source;
+                match rolls(&self.draft.rolls, source) {
+                    Ok(ref rolls)
+                        if self.draft.rolls_only && roll_bits(rolls, source) < how_many.bits() =>
+                    {
+                        *error = Some(format!(
+                            "Not enough rolls to use them alone: {} of {} bits",
+                            roll_bits(rolls, source),
+                            how_many.bits()
+                        ))
+                    }
+                    Ok(rolls) => {
+                        let data = entropy_from_rolls(&rolls, how_many, !self.draft.rolls_only);
+                        self.draft.words =
+                            mnemonic(&data, self.draft.language).expect("couldn't create mnemonic");
+                        self.draft.backup_verified = false;
+                        self.step = Step::DisplayWords;
                     }
+                    Err(Error::InvalidRoll(position)) => {
+                        let typed = self.draft.rolls.chars().nth(position).unwrap_or_default();
+                        *error = Some(format!(
+                            "'{}' at character {} isn't a valid roll",
+                            typed,
+                            position + 1
+                        ))
+                    }
+                    Err(e) => *error = Some(format!("{:?}", e)),
                 }
-                Step::HowManyWords { network, name, .. } => {
-                    self.step = Step::Name {
-                        network,
-                        name,
-                        input_state: text_input::State::new(),
+            }
+            Step::DisplayWords if self.draft.split => {
+                if self.draft.groups.is_empty() {
+                    self.draft.group_threshold = "1".to_string();
+                    self.draft.groups.push(("2".to_string(), "3".to_string()));
+                }
+                self.step = Step::shamir_groups(self.draft.groups.len());
+            }
+            Step::DisplayWords => self.step = Step::verify(&self.draft.words),
+            Step::ShamirGroups { ref mut error, .. } => {
+                let parse = |text: &str| text.trim().parse::<u8>().ok();
+                let groups: Option<Vec<(u8, u8)>> = self
+                    .draft
+                    .groups
+                    .iter()
+                    .map(|(threshold, count)| Some((parse(threshold)?, parse(count)?)))
+                    .collect();
+                match (parse(&self.draft.group_threshold), groups) {
+                    (Some(group_threshold), Some(groups)) => {
+                        let data = entropy(&self.draft.words, self.draft.language)
+                            .expect("generated words are valid");
+                        match shamir::split(&data, "", group_threshold, &groups) {
+                            Ok(shares) => {
+                                self.draft.shares = shares;
+                                self.step = Step::ShamirShares;
+                            }
+                            Err(Error::Shamir(message)) => *error = Some(message),
+                            Err(e) => *error = Some(format!("{:?}", e)),
+                        }
                     }
+                    _ => *error = Some("Counts must be whole numbers".to_string()),
                 }
-                Step::DisplayWords { network, name, .. } => {
-                    self.step = Step::HowManyWords {
-                        network,
-                        name,
-                        how_many: None,
+            }
+            Step::ShamirShares => self.step = Step::shares(),
+            Step::Shares {
+                ref shares,
+                ref mut error,
+                ..
+            } => match recover_shares(shares) {
+                Ok(data) if self.draft.source() == Some(Method::Shamir) => {
+                    match mnemonic(&data, self.draft.language) {
+                        Ok(words) => {
+                            self.draft.words = words;
+                            // They hold the shares, so they clearly have a backup
+                            self.draft.backup_verified = true;
+                            self.step = Step::passphrase();
+                        }
+                        Err(Error::Mnemonic(message)) => *error = Some(message),
+                        Err(e) => *error = Some(format!("{:?}", e)),
+                    }
+                }
+                Ok(data) => {
+                    if entropy(&self.draft.words, self.draft.language).ok() == Some(data) {
+                        self.draft.backup_verified = true;
+                        self.step = Step::passphrase();
+                    } else {
+                        *error = Some("These shares are from a different backup".to_string());
                     }
                 }
+                Err(message) => *error = Some(message),
             },
-            Message::NetworkSelected(network) => {
-                if let Step::Network { .. } = self.step {
-                    self.step = Step::Network {
-                        network: Some(network),
+            Step::Verify {
+                ref positions,
+                ref answers,
+                ref mut error,
+                ..
+            } => {
+                let correct = positions.iter().zip(answers).all(|(&position, answer)| {
+                    normalize(answer.trim()) == normalize(&self.draft.words[position])
+                });
+                if correct {
+                    self.draft.backup_verified = true;
+                    self.step = Step::passphrase();
+                } else {
+                    *error = Some("Some words don't match. Check your backup.".to_string());
+                }
+            }
+            Step::Restore {
+                ref phrase,
+                ref mut error,
+                ..
+            } => {
+                let words: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+                match entropy(&words, self.draft.language) {
+                    Ok(_) => {
+                        self.draft.words = words;
+                        // They typed the words in, so they clearly have them
+                        self.draft.backup_verified = true;
+                        self.step = Step::passphrase();
+                    }
+                    Err(Error::InvalidWord(position)) => {
+                        *error = Some(format!(
+                            "Word {} (\"{}\") isn't in the word list",
+                            position + 1,
+                            words[position]
+                        ))
                     }
+                    Err(Error::InvalidChecksum) => {
+                        *error = Some(
+                            "Checksum doesn't match. Check the words and their order.".to_string(),
+                        )
+                    }
+                    Err(Error::Mnemonic(message)) => *error = Some(message),
+                    Err(e) => *error = Some(format!("{:?}", e)),
                 }
             }
+            Step::Passphrase {
+                ref confirm,
+                ref mut error,
+                ..
+            } => {
+                // A typo here would make the wallet unrecoverable, so make them type it twice
+                if *confirm != self.draft.passphrase {
+                    *error = Some("Passphrases don't match".to_string());
+                } else if self.draft.is_multisig() {
+                    self.step = Step::derivation();
+                } else {
+                    self.step = Step::ScriptType;
+                }
+            }
+            Step::ScriptType => self.step = Step::derivation(),
+            Step::Derivation { ref mut error, .. } => match self.draft.account_index() {
+                None => *error = Some("Account number must be a whole number".to_string()),
+                Some(index) if self.draft.is_multisig() => {
+                    let network = self.draft.network.expect("network was chosen");
+                    let master = master_key(&self.draft.words, &self.draft.passphrase, network);
+                    let label = match self.draft.adding {
+                        Some(Method::Restore) => "Restored seed",
+                        _ => "New seed",
+                    };
+                    let result = Signer::from_master(
+                        &master,
+                        self.draft.derivation_path(index),
+                        label.into(),
+                    )
+                    .map_err(|e| format!("{:?}", e))
+                    .and_then(|signer| self.add_signer(signer));
+                    if let (Err(message), Step::Derivation { ref mut error, .. }) =
+                        (result, &mut self.step)
+                    {
+                        *error = Some(message);
+                    }
+                }
+                Some(_) => {
+                    if self.draft.sheet_path.is_empty() {
+                        self.draft.sheet_path = default_sheet_path(&self.draft.name);
+                    }
+                    self.step = self.sheet_step();
+                }
+            },
+            Step::BackupSheet { .. }
+                if self.draft.is_multisig() && !self.draft.has_private_signer() =>
+            {
+                // Nothing secret to encrypt, so we're done
+                let threshold = self.draft.threshold.trim().parse().unwrap_or(1);
+                let account = Account::new(
+                    self.draft.name.clone(),
+                    self.draft.network.expect("network was chosen"),
+                    multisig_descriptor(threshold, &self.draft.signers, 0),
+                    Some(multisig_descriptor(threshold, &self.draft.signers, 1)),
+                );
+                return Command::perform(async move { account }, Message::SetupComplete);
+            }
+            Step::BackupSheet { .. } => self.step = Step::password(),
+            Step::Password {
+                ref password,
+                ref confirm,
+                ref mut error,
+                ref mut encrypting,
+                ..
+            } => {
+                if *encrypting {
+                    return Command::none();
+                }
+                // Multisig signers were each verified before being added
+                if !self.draft.backup_verified && !self.draft.is_multisig() {
+                    *error = Some("Backup hasn't been verified".to_string());
+                } else if password.is_empty() {
+                    *error = Some("Password can't be empty".to_string());
+                } else if password != confirm {
+                    *error = Some("Passwords don't match".to_string());
+                } else {
+                    *encrypting = true;
+                    let network = self.draft.network.expect("network was chosen");
+                    let (descriptor, change_descriptor) = if self.draft.is_multisig() {
+                        // Already validated on the signers step
+                        let threshold = self.draft.threshold.trim().parse().unwrap_or(1);
+                        (
+                            multisig_descriptor(threshold, &self.draft.signers, 0),
+                            Some(multisig_descriptor(threshold, &self.draft.signers, 1)),
+                        )
+                    } else {
+                        let master = master_key(&self.draft.words, &self.draft.passphrase, network);
+                        // Already validated on the derivation step
+                        let index = self.draft.account_index().unwrap_or(0);
+                        let (descriptor, change_descriptor) = descriptors(
+                            &master,
+                            self.draft.derivation_path(index),
+                            self.draft.script_type,
+                        );
+                        (descriptor, Some(change_descriptor))
+                    };
+                    // Deriving the encryption key is slow, so do it off the UI thread.
+                    // SetupComplete is intercepted in main once it resolves.
+                    return Command::perform(
+                        tasks::encrypt_account(
+                            Account::new(
+                                self.draft.name.clone(),
+                                network,
+                                descriptor.clone(),
+                                change_descriptor.clone(),
+                            ),
+                            descriptor,
+                            change_descriptor,
+                            password.clone(),
+                        ),
+                        Message::SetupComplete,
+                    );
+                }
+            }
+        };
+        Command::none()
+    }
+    fn back(&mut self) {
+        self.step = match self.step {
+            Step::Network => {
+                println!("Can't go back from step 1");
+                return;
+            }
+            Step::Name { .. } => Step::Network,
+            Step::Method => Step::name(),
+            Step::HowManyWords | Step::Restore { .. } => Step::Language,
+            Step::Language | Step::Import { .. } => {
+                if self.draft.is_multisig() {
+                    self.draft.adding = None;
+                    Step::signers(&self.draft.signers)
+                } else {
+                    Step::Method
+                }
+            }
+            Step::Rolls { .. } => Step::HowManyWords,
+            Step::DisplayWords => match self.draft.entropy_source {
+                EntropySource::System => Step::HowManyWords,
+                _ => Step::rolls(),
+            },
+            Step::Verify { .. } | Step::ShamirGroups { .. } => Step::DisplayWords,
+            Step::ShamirShares => Step::shamir_groups(self.draft.groups.len()),
+            Step::Shares { .. } => match self.draft.source() {
+                Some(Method::Shamir) => Step::Language,
+                _ => Step::ShamirShares,
+            },
+            Step::Passphrase { .. } => match self.draft.source() {
+                Some(Method::Restore) => Step::restore(&self.draft.words),
+                Some(Method::Shamir) => Step::shares(),
+                _ => Step::DisplayWords,
+            },
+            Step::ScriptType => Step::passphrase(),
+            Step::Derivation { .. } if self.draft.is_multisig() => Step::passphrase(),
+            Step::Derivation { .. } => Step::ScriptType,
+            Step::BackupSheet { .. } if self.draft.is_multisig() => {
+                Step::signers(&self.draft.signers)
+            }
+            Step::BackupSheet { .. } => Step::derivation(),
+            Step::Password { .. } => self.sheet_step(),
+            Step::Signers { .. } => Step::Method,
+            Step::AccountSigner { .. } => Step::signers(&self.draft.signers),
+        }
+    }
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::Next => return self.next(),
+            Message::Back => self.back(),
+            Message::NetworkSelected(network) => self.draft.network = Some(network),
             Message::Name(name) => {
-                if let Step::Name {
-                    network,
-                    input_state,
+                self.draft.name = name;
+                if let Step::Name { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::MethodSelected(method) => {
+                if self.draft.method != Some(method) {
+                    self.draft.words.clear();
+                    self.draft.signers.clear();
+                }
+                self.draft.method = Some(method);
+            }
+            Message::AddSigner(source) => {
+                self.draft.adding = Some(source);
+                self.draft.words.clear();
+                self.draft.passphrase.clear();
+                self.step = Self::key_step(source);
+            }
+            Message::AddAccountSigner => self.step = Step::account_signer(),
+            Message::RemoveSigner(index) => {
+                if index < self.draft.signers.len() {
+                    self.draft.signers.remove(index);
+                    self.step = Step::signers(&self.draft.signers);
+                }
+            }
+            Message::ScriptTypeSelected(script_type) => self.draft.script_type = script_type,
+            Message::AccountIndex(account_index) => {
+                self.draft.account_index = account_index;
+                if let Step::Derivation { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::Threshold(threshold) => {
+                self.draft.threshold = threshold;
+                if let Step::Signers { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::SignerAccountSelected(index) => {
+                if let Step::AccountSigner {
+                    ref mut selected,
+                    ref mut error,
                     ..
-                } = self.step.clone()
+                } = self.step
                 {
-                    self.step = Step::Name {
-                        network,
-                        input_state,
-                        name,
-                    };
+                    *selected = Some(index);
+                    *error = None;
                 }
             }
-            Message::HowManyWords(how_many) => {
-                if let Step::HowManyWords { network, name, .. } = self.step.clone() {
-                    self.step = Step::HowManyWords {
-                        network,
-                        name,
-                        how_many: Some(how_many),
-                    };
+            Message::SignerPassword(new_password) => {
+                if let Step::AccountSigner {
+                    ref mut password,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    *password = new_password;
+                    *error = None;
+                }
+            }
+            Message::AccountSignerLoaded(result) => {
+                let result = match result {
+                    Ok(signer) => self.add_signer(signer),
+                    Err(Error::WrongPassword) => Err("Wrong password".to_string()),
+                    Err(Error::Import(message)) => Err(message),
+                    Err(e) => Err(format!("{:?}", e)),
+                };
+                if let Step::AccountSigner {
+                    ref mut error,
+                    ref mut decrypting,
+                    ..
+                } = self.step
+                {
+                    *decrypting = false;
+                    if let Err(message) = result {
+                        *error = Some(message);
+                    }
+                }
+            }
+            Message::LanguageSelected(language) => {
+                if self.draft.language != language {
+                    self.draft.words.clear();
+                }
+                self.draft.language = language;
+            }
+            Message::HowManyWords(how_many) => self.draft.how_many = Some(how_many),
+            Message::EntropySourceSelected(source) => {
+                if self.draft.entropy_source != source {
+                    self.draft.rolls.clear();
+                }
+                self.draft.entropy_source = source;
+            }
+            Message::Rolls(rolls) => {
+                self.draft.rolls = rolls;
+                if let Step::Rolls { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::RollsOnly(rolls_only) => {
+                self.draft.rolls_only = rolls_only;
+                if let Step::Rolls { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::SplitShares(split) => self.draft.split = split,
+            Message::GroupThreshold(threshold) => {
+                self.draft.group_threshold = threshold;
+                if let Step::ShamirGroups { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::MemberThreshold(index, threshold) => {
+                if let Some(group) = self.draft.groups.get_mut(index) {
+                    group.0 = threshold;
+                }
+                if let Step::ShamirGroups { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::MemberCount(index, count) => {
+                if let Some(group) = self.draft.groups.get_mut(index) {
+                    group.1 = count;
+                }
+                if let Step::ShamirGroups { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::AddGroup => {
+                if self.draft.groups.len() < MAX_SHARES as usize {
+                    self.draft.groups.push(("2".to_string(), "3".to_string()));
+                    self.step = Step::shamir_groups(self.draft.groups.len());
+                }
+            }
+            Message::RemoveGroup(index) => {
+                if index < self.draft.groups.len() && self.draft.groups.len() > 1 {
+                    self.draft.groups.remove(index);
+                    self.step = Step::shamir_groups(self.draft.groups.len());
+                }
+            }
+            Message::Share(index, share) => {
+                if let Step::Shares {
+                    ref mut shares,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    shares[index] = share;
+                    *error = None;
+                }
+            }
+            Message::AddShare => {
+                if let Step::Shares {
+                    ref mut inputs,
+                    ref mut shares,
+                    ..
+                } = self.step
+                {
+                    inputs.push(text_input::State::new());
+                    shares.push("".to_string());
+                }
+            }
+            Message::VerifyWord(index, answer) => {
+                if let Step::Verify {
+                    ref mut answers,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    answers[index] = answer;
+                    *error = None;
+                }
+            }
+            Message::SkipVerification => {
+                if let (Step::Verify { .. }, Some(Network::Regtest)) =
+                    (&self.step, self.draft.network)
+                {
+                    warn!(
+                        "Skipping seed backup verification for regtest account \"{}\"",
+                        self.draft.name
+                    );
+                    self.draft.backup_verified = true;
+                    self.step = Step::passphrase();
+                }
+            }
+            Message::Phrase(new_phrase) => {
+                if let Step::Restore {
+                    ref mut phrase,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    *phrase = new_phrase;
+                    *error = None;
+                }
+            }
+            Message::Suggestion(word) => {
+                if let Step::Restore { ref mut phrase, .. } = self.step {
+                    // Replace the partially typed word
+                    let start = phrase.rfind(' ').map(|i| i + 1).unwrap_or(0);
+                    phrase.truncate(start);
+                    phrase.push_str(&word);
+                    phrase.push(' ');
+                }
+            }
+            Message::Passphrase(passphrase) => {
+                self.draft.passphrase = passphrase;
+                if let Step::Passphrase { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::ConfirmPassphrase(new_confirm) => {
+                if let Step::Passphrase {
+                    ref mut confirm,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    *confirm = new_confirm;
+                    *error = None;
+                }
+            }
+            Message::SheetPath(path) => {
+                self.draft.sheet_path = path;
+                if let Step::BackupSheet { ref mut error, .. } = self.step {
+                    *error = None;
+                }
+            }
+            Message::SaveSheet => {
+                if let Step::BackupSheet {
+                    sheet: Some(ref sheet),
+                    ref mut saving,
+                    ..
+                } = self.step
+                {
+                    if !*saving && !self.draft.sheet_path.trim().is_empty() {
+                        *saving = true;
+                        return Command::perform(
+                            tasks::save_backup_sheet(
+                                PathBuf::from(self.draft.sheet_path.trim()),
+                                sheet.clone(),
+                            ),
+                            Message::SheetSaved,
+                        );
+                    }
+                }
+            }
+            Message::SheetSaved(result) => {
+                if let Step::BackupSheet {
+                    ref mut saved,
+                    ref mut error,
+                    ref mut saving,
+                    ..
+                } = self.step
+                {
+                    *saving = false;
+                    match result {
+                        Ok(path) => *saved = Some(path),
+                        Err(Error::File(message)) => *error = Some(message),
+                        Err(e) => *error = Some(format!("{:?}", e)),
+                    }
+                }
+            }
+            Message::Password(new_password) => {
+                if let Step::Password {
+                    ref mut password,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    *password = new_password;
+                    *error = None;
+                }
+            }
+            Message::ConfirmPassword(new_confirm) => {
+                if let Step::Password {
+                    ref mut confirm,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    *confirm = new_confirm;
+                    *error = None;
+                }
+            }
+            Message::Import(new_text) => {
+                if let Step::Import {
+                    ref mut text,
+                    ref mut error,
+                    ..
+                } = self.step
+                {
+                    *text = new_text;
+                    *error = None;
                 }
             }
             // This is handled above
@@ -180,7 +1271,7 @@ impl<'a> Page {
         // Back button
         match self.step.clone() {
             // First step can't go back
-            Step::Network { .. } => {}
+            Step::Network => {}
             _ => {
                 controls =
                     controls.push(button(&mut self.back_button, "Back").on_press(Message::Back));
@@ -188,31 +1279,184 @@ impl<'a> Page {
         }
 
         // Next button
-        match self.step.clone() {
-            Step::DisplayWords {
-                name, descriptor, ..
-            } => {
-                let account = Account::new(name, descriptor);
-                controls = controls.push(
-                    //Button::new(self.next_button, Text::new("Finish"))
-                    //.on_press(Message::SetupComplete(account)),
-                    button(&mut self.next_button, "Finish")
-                        .on_press(Message::SetupComplete(account)),
-                );
-            }
-            _ => {
-                controls =
-                    controls.push(button(&mut self.next_button, "Next").on_press(Message::Next));
-            }
-        }
+        let finishes = match self.step {
+            Step::Password { .. } => true,
+            Step::Import { .. } => !self.draft.is_multisig(),
+            Step::Signers { .. } => !self.draft.has_private_signer(),
+            _ => false,
+        };
+        let label = if finishes { "Finish" } else { "Next" };
+        controls = controls.push(button(&mut self.next_button, label).on_press(Message::Next));
 
+        let draft = &self.draft;
+        let accounts = &self.accounts;
         let content = match &mut self.step {
-            Step::Network { network } => Self::network(*network),
-            Step::Name {
-                input_state, name, ..
-            } => Self::name(input_state, name),
-            Step::HowManyWords { how_many, .. } => Self::how_many_words(*how_many),
-            Step::DisplayWords { words, .. } => Self::display_words(words.clone()),
+            Step::Network => Self::network(draft.network),
+            Step::Name { input_state, error } => {
+                Self::name(input_state, &draft.name, error.clone())
+            }
+            Step::Method => Self::method(draft.method),
+            Step::Language => Self::language(draft.language),
+            Step::HowManyWords => Self::how_many_words(draft.how_many, draft.entropy_source),
+            Step::Rolls { input_state, error } => Self::rolls(
+                input_state,
+                &draft.rolls,
+                draft.entropy_source,
+                draft.how_many.expect("words were chosen"),
+                draft.rolls_only,
+                error.clone(),
+            ),
+            Step::DisplayWords => Self::display_words(draft.words.clone(), draft.split),
+            Step::ShamirGroups {
+                threshold_input,
+                inputs,
+                add_button,
+                remove_buttons,
+                error,
+            } => Self::shamir_groups(
+                threshold_input,
+                &draft.group_threshold,
+                inputs,
+                &draft.groups,
+                add_button,
+                remove_buttons,
+                error.clone(),
+            ),
+            Step::ShamirShares => Self::shamir_shares(&draft.shares, &draft.groups),
+            Step::Shares {
+                inputs,
+                shares,
+                add_button,
+                error,
+            } => Self::shares(
+                inputs,
+                shares,
+                add_button,
+                draft.source() == Some(Method::Shamir),
+                error.clone(),
+            ),
+            Step::Verify {
+                positions,
+                inputs,
+                answers,
+                skip_button,
+                error,
+            } => Self::verify(
+                positions,
+                inputs,
+                answers,
+                // Only regtest coins are worthless enough to skip this
+                match draft.network {
+                    Some(Network::Regtest) => Some(skip_button),
+                    _ => None,
+                },
+                error.clone(),
+            ),
+            Step::Restore {
+                phrase_input,
+                phrase,
+                suggestion_buttons,
+                error,
+            } => Self::restore(
+                phrase_input,
+                phrase,
+                suggestion_buttons,
+                draft.language,
+                error.clone(),
+            ),
+            Step::Import {
+                input_state,
+                text,
+                error,
+            } => Self::import(input_state, text, error.clone(), self.draft.is_multisig()),
+            Step::Passphrase {
+                passphrase_input,
+                confirm_input,
+                confirm,
+                error,
+            } => Self::passphrase(
+                passphrase_input,
+                &draft.passphrase,
+                confirm_input,
+                confirm,
+                error.clone(),
+            ),
+            Step::ScriptType => Self::script_type(draft.script_type),
+            Step::Derivation { index_input, error } => {
+                let path = draft
+                    .account_index()
+                    .map(|index| draft.derivation_path(index));
+                Self::derivation(index_input, &draft.account_index, path, error.clone())
+            }
+            Step::BackupSheet {
+                sheet,
+                path_input,
+                save_button,
+                saved,
+                error,
+                saving,
+            } => Self::backup_sheet(
+                sheet.is_some(),
+                draft,
+                path_input,
+                save_button,
+                saved.as_ref(),
+                error.clone(),
+                *saving,
+            ),
+            Step::Password {
+                password_input,
+                password,
+                confirm_input,
+                confirm,
+                error,
+                encrypting,
+                ..
+            } => Self::password(
+                password_input,
+                password,
+                confirm_input,
+                confirm,
+                error.clone(),
+                *encrypting,
+            ),
+            Step::Signers {
+                generate_button,
+                restore_button,
+                import_button,
+                account_button,
+                remove_buttons,
+                threshold_input,
+                error,
+            } => Self::signers(
+                &draft.signers,
+                accounts,
+                [
+                    generate_button,
+                    restore_button,
+                    import_button,
+                    account_button,
+                ],
+                remove_buttons,
+                threshold_input,
+                &draft.threshold,
+                error.clone(),
+            ),
+            Step::AccountSigner {
+                selected,
+                password_input,
+                password,
+                error,
+                decrypting,
+            } => Self::account_signer(
+                accounts,
+                draft.network,
+                *selected,
+                password_input,
+                password,
+                error.clone(),
+                *decrypting,
+            ),
         };
 
         // TODO: put the controls outside the scrollable
@@ -254,19 +1498,116 @@ impl<'a> Page {
             .push(question)
             .into()
     }
-    fn name(input_state: &'a mut text_input::State, name: &str) -> Element<'a, Message> {
+    fn name(
+        input_state: &'a mut text_input::State,
+        name: &str,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
         let input = TextInput::new(input_state, "Name", name, Message::Name)
             .padding(15)
             .size(30);
 
-        Column::new()
+        let mut content = Column::new()
             .padding(20)
             .align_items(Align::Center)
             .push(Text::new("Name").size(50))
-            .push(input)
+            .push(input);
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn method(selection: Option<Method>) -> Element<'a, Message> {
+        let choices = Method::all().iter().cloned().fold(
+            Column::new().padding(10).spacing(20),
+            |choices, method| {
+                choices.push(Radio::new(
+                    method,
+                    method,
+                    selection,
+                    Message::MethodSelected,
+                ))
+            },
+        );
+        Column::new()
+            .padding(20)
+            .align_items(Align::Center)
+            .push(Text::new("Keys").size(50))
+            .push(choices)
             .into()
     }
-    fn how_many_words(how_many_words: Option<Entropy>) -> Element<'a, Message> {
+    fn language(selection: Language) -> Element<'a, Message> {
+        let choices = Language::all().iter().cloned().fold(
+            Column::new().padding(10).spacing(20),
+            |choices, language| {
+                choices.push(Radio::new(
+                    language,
+                    language,
+                    Some(selection),
+                    Message::LanguageSelected,
+                ))
+            },
+        );
+        Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Word List").size(50))
+            .push(Text::new(
+                "Restoring needs the same language, and not every wallet supports them all. \
+                 English is the safest choice.",
+            ))
+            .push(choices)
+            .into()
+    }
+    fn restore(
+        phrase_input: &'a mut text_input::State,
+        phrase: &str,
+        suggestion_buttons: &'a mut [button::State],
+        language: Language,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let input = TextInput::new(
+            phrase_input,
+            "Enter your 12, 18 or 24 words",
+            phrase,
+            Message::Phrase,
+        )
+        .padding(15)
+        .size(30);
+
+        // Autocomplete whatever word is being typed
+        let partial = if phrase.ends_with(' ') {
+            ""
+        } else {
+            phrase.split_whitespace().last().unwrap_or("")
+        };
+        let words = suggestions(partial, SUGGESTIONS, language);
+        let suggestions = suggestion_buttons.iter_mut().zip(words).fold(
+            Row::new().spacing(10),
+            |row, (state, word)| {
+                row.push(button(state, word).on_press(Message::Suggestion(word.to_string())))
+            },
+        );
+
+        let count = phrase.split_whitespace().count();
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Restore").size(50))
+            .push(input)
+            .push(suggestions)
+            .push(Text::new(format!("{} words", count)));
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn how_many_words(
+        how_many_words: Option<Entropy>,
+        entropy_source: EntropySource,
+    ) -> Element<'a, Message> {
         let radio = Column::new()
             .padding(20)
             .spacing(10)
@@ -287,9 +1628,73 @@ impl<'a> Page {
             .align_items(Align::Center)
             .push(Text::new("How Many Words").size(50))
             .push(radio)
+            .push(Text::new("Randomness from"))
+            .push(EntropySource::all().iter().cloned().fold(
+                Column::new().padding(10).spacing(20),
+                |choices, source| {
+                    choices.push(Radio::new(
+                        source,
+                        source,
+                        Some(entropy_source),
+                        Message::EntropySourceSelected,
+                    ))
+                },
+            ))
             .into()
     }
-    fn display_words(words: Vec<String>) -> Element<'a, Message> {
+    fn rolls(
+        input_state: &'a mut text_input::State,
+        rolls_text: &str,
+        source: EntropySource,
+        how_many: Entropy,
+        rolls_only: bool,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let (title, placeholder) = match source {
+            EntropySource::Coins => ("Coin Flips", "H or T for each flip"),
+            _ => ("Dice Rolls", "1 to 6 for each roll"),
+        };
+        let input = TextInput::new(input_state, placeholder, rolls_text, Message::Rolls)
+            .padding(15)
+            .size(30);
+        let progress = match rolls(rolls_text, source) {
+            Ok(valid) => format!(
+                "{} of {} bits collected",
+                roll_bits(&valid, source),
+                how_many.bits()
+            ),
+            Err(_) => "Only valid rolls are counted".to_string(),
+        };
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new(title).size(50))
+            .push(input)
+            .push(Text::new(progress))
+            .push(Checkbox::new(
+                rolls_only,
+                "Use only my rolls, not this computer's randomness",
+                Message::RollsOnly,
+            ));
+        if rolls_only {
+            content = content.push(Text::new(
+                "Warning: your seed will be exactly as random as your rolls. Biased dice, \
+                 repeated patterns or too few rolls let others guess it and steal your coins.",
+            ));
+        } else {
+            content = content.push(Text::new(
+                "Your rolls are mixed with this computer's randomness, so the seed stays safe \
+                 even if one of them is flawed.",
+            ));
+        }
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn display_words(words: Vec<String>, split: bool) -> Element<'a, Message> {
         let word_list = Column::new().padding(20).spacing(10).push(
             words
                 .iter()
@@ -303,6 +1708,567 @@ impl<'a> Page {
             .align_items(Align::Center)
             .push(Text::new("Display Words").size(50))
             .push(word_list)
+            .push(Checkbox::new(
+                split,
+                "Also split into Shamir shares (SLIP-39)",
+                Message::SplitShares,
+            ))
+            .into()
+    }
+    fn shamir_groups(
+        threshold_input: &'a mut text_input::State,
+        group_threshold: &str,
+        inputs: &'a mut [(text_input::State, text_input::State)],
+        groups: &[(String, String)],
+        add_button: &'a mut button::State,
+        remove_buttons: &'a mut [button::State],
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let removable = groups.len() > 1;
+        let rows = inputs
+            .iter_mut()
+            .zip(remove_buttons)
+            .zip(groups)
+            .enumerate()
+            .fold(
+                Column::new().spacing(10),
+                |rows, (index, (((threshold_input, count_input), remove_button), group))| {
+                    let mut row = Row::new()
+                        .spacing(10)
+                        .align_items(Align::Center)
+                        .push(Text::new(format!("Group {} needs", index + 1)))
+                        .push(
+                            TextInput::new(threshold_input, "2", &group.0, move |threshold| {
+                                Message::MemberThreshold(index, threshold)
+                            })
+                            .padding(10)
+                            .width(Length::Units(60)),
+                        )
+                        .push(Text::new("of"))
+                        .push(
+                            TextInput::new(count_input, "3", &group.1, move |count| {
+                                Message::MemberCount(index, count)
+                            })
+                            .padding(10)
+                            .width(Length::Units(60)),
+                        )
+                        .push(Text::new("shares"));
+                    if removable {
+                        row = row.push(
+                            button(remove_button, "Remove").on_press(Message::RemoveGroup(index)),
+                        );
+                    }
+                    rows.push(row)
+                },
+            );
+        let threshold = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Recover with"))
+            .push(
+                TextInput::new(
+                    threshold_input,
+                    "1",
+                    group_threshold,
+                    Message::GroupThreshold,
+                )
+                .padding(10)
+                .width(Length::Units(60)),
+            )
+            .push(Text::new(format!("of {} groups", groups.len())));
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Shamir Groups").size(50))
+            .push(Text::new(
+                "Give each group's shares to different people. Any share alone reveals nothing \
+                 about the seed.",
+            ))
+            .push(rows)
+            .push(button(add_button, "Add group").on_press(Message::AddGroup))
+            .push(threshold);
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn shamir_shares(shares: &[Vec<String>], groups: &[(String, String)]) -> Element<'a, Message> {
+        let list = shares.iter().zip(groups).enumerate().fold(
+            Column::new().padding(10).spacing(20),
+            |list, (index, (members, (threshold, _)))| {
+                let title = format!(
+                    "Group {}, any {} of these {}",
+                    index + 1,
+                    threshold.trim(),
+                    members.len()
+                );
+                members.iter().enumerate().fold(
+                    list.push(Text::new(title).size(24)),
+                    |list, (number, share)| {
+                        list.push(Text::new(format!("{}. {}", number + 1, share)).size(16))
+                    },
+                )
+            },
+        );
+        Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Shamir Shares").size(50))
+            .push(Text::new(
+                "Write each share down on its own. Next you'll enter enough of them to prove \
+                 they recover your seed.",
+            ))
+            .push(list)
             .into()
     }
+    fn shares(
+        inputs: &'a mut [text_input::State],
+        shares: &[String],
+        add_button: &'a mut button::State,
+        restoring: bool,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let fields = inputs.iter_mut().zip(shares).enumerate().fold(
+            Column::new().padding(10).spacing(20),
+            |fields, (index, (input, share))| {
+                fields.push(
+                    TextInput::new(
+                        input,
+                        &format!("Share #{}", index + 1),
+                        share,
+                        move |share| Message::Share(index, share),
+                    )
+                    .padding(15)
+                    .size(20),
+                )
+            },
+        );
+        let (title, help) = if restoring {
+            (
+                "Restore From Shares",
+                "Enter enough shares from enough groups, in any order. Only shares made by \
+                 this wallet give back the same seed words.",
+            )
+        } else {
+            (
+                "Verify Shares",
+                "Enter enough shares from enough groups to recover your seed",
+            )
+        };
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new(title).size(50))
+            .push(Text::new(help))
+            .push(fields)
+            .push(button(add_button, "Add share").on_press(Message::AddShare));
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn verify(
+        positions: &[usize],
+        inputs: &'a mut [text_input::State],
+        answers: &[String],
+        skip_button: Option<&'a mut button::State>,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let questions = inputs
+            .iter_mut()
+            .zip(positions.iter().zip(answers))
+            .enumerate()
+            .fold(
+                Column::new().padding(10).spacing(20),
+                |questions, (index, (input, (position, answer)))| {
+                    questions.push(
+                        TextInput::new(
+                            input,
+                            &format!("Word #{}", position + 1),
+                            answer,
+                            move |answer| Message::VerifyWord(index, answer),
+                        )
+                        .padding(15)
+                        .size(30),
+                    )
+                },
+            );
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Verify Backup").size(50))
+            .push(Text::new("Enter these words from your backup"))
+            .push(questions);
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        if let Some(skip_button) = skip_button {
+            content = content.push(
+                button(skip_button, "Skip (regtest only)").on_press(Message::SkipVerification),
+            );
+        }
+        content.into()
+    }
+    fn import(
+        input_state: &'a mut text_input::State,
+        text: &str,
+        error: Option<String>,
+        multisig: bool,
+    ) -> Element<'a, Message> {
+        let (title, placeholder, description) = if multisig {
+            (
+                "Import xpub",
+                "[fingerprint/48'/0'/0'/2']xpub",
+                "Paste the key with its origin as your hardware wallet exports it, so it can \
+                 recognise its key when signing.",
+            )
+        } else {
+            (
+                "Watch-only",
+                "xpub, ypub, zpub or output descriptor",
+                "Watch-only accounts track balances and addresses but can't sign. Sending \
+                 creates an unsigned PSBT for another wallet to sign.",
+            )
+        };
+        let input = TextInput::new(input_state, placeholder, text, Message::Import)
+            .padding(15)
+            .size(20);
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new(title).size(50))
+            .push(Text::new(description))
+            .push(input);
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn passphrase(
+        passphrase_input: &'a mut text_input::State,
+        passphrase: &str,
+        confirm_input: &'a mut text_input::State,
+        confirm: &str,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let passphrase_input = TextInput::new(
+            passphrase_input,
+            "Passphrase (optional)",
+            passphrase,
+            Message::Passphrase,
+        )
+        .password()
+        .padding(15)
+        .size(30);
+        let confirm_input = TextInput::new(
+            confirm_input,
+            "Confirm passphrase",
+            confirm,
+            Message::ConfirmPassphrase,
+        )
+        .password()
+        .padding(15)
+        .size(30);
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Passphrase").size(50))
+            .push(Text::new(
+                "An optional extra word mixed into your seed. Anyone restoring this wallet needs \
+                 both the words and the passphrase, so back it up too. Leave blank to skip.",
+            ))
+            .push(passphrase_input)
+            .push(confirm_input);
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn script_type(selection: ScriptType) -> Element<'a, Message> {
+        let choices = ScriptType::all().iter().cloned().fold(
+            Column::new().padding(10).spacing(20),
+            |choices, script_type| {
+                choices.push(Radio::new(
+                    script_type,
+                    script_type,
+                    Some(selection),
+                    Message::ScriptTypeSelected,
+                ))
+            },
+        );
+        Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Address Type").size(50))
+            .push(choices)
+            .push(Text::new(
+                "Native segwit has the lowest fees, but a few older services can't send to \
+                 bc1 addresses.",
+            ))
+            .push(Text::new(
+                "Nested segwit works with every service and still saves most of the segwit fee \
+                 discount. Pick it if an exchange rejects bc1 addresses.",
+            ))
+            .push(Text::new(
+                "Legacy has the highest fees. Only use it for services that accept nothing else.",
+            ))
+            .into()
+    }
+    fn derivation(
+        index_input: &'a mut text_input::State,
+        account_index: &str,
+        path: Option<DerivationPath>,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let input = TextInput::new(index_input, "0", account_index, Message::AccountIndex)
+            .padding(15)
+            .size(30);
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Account Number").size(50))
+            .push(Text::new(
+                "One seed can hold many separate accounts. Use 0 unless this seed already has \
+                 an account you want to keep apart from this one.",
+            ))
+            .push(input);
+        if let Some(path) = path {
+            content = content.push(Text::new(format!("Derivation path: {}", path)));
+        }
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn backup_sheet(
+        ready: bool,
+        draft: &Draft,
+        path_input: &'a mut text_input::State,
+        save_button: &'a mut button::State,
+        saved: Option<&PathBuf>,
+        error: Option<String>,
+        saving: bool,
+    ) -> Element<'a, Message> {
+        let input =
+            TextInput::new(path_input, "File", &draft.sheet_path, Message::SheetPath).padding(15);
+        let save: Element<_> = if saving {
+            Text::new("Saving").into()
+        } else if ready {
+            button(save_button, "Save")
+                .on_press(Message::SaveSheet)
+                .into()
+        } else {
+            button(save_button, "Save").into()
+        };
+
+        let description = if draft.is_multisig() {
+            "Save a printable sheet with the cosigner fingerprints and descriptors. You need it \
+             alongside enough of the signers' own backups to recover the account."
+        } else {
+            "Save a printable sheet with your words, master fingerprint and descriptors. \
+             It holds your seed, so print it straight away and delete the file."
+        };
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Backup Sheet").size(50))
+            .push(Text::new(description))
+            .push(input)
+            .push(save);
+        if let Some(path) = saved {
+            content = content.push(Text::new(format!("Saved to {}", path.display())));
+        }
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn password(
+        password_input: &'a mut text_input::State,
+        password: &str,
+        confirm_input: &'a mut text_input::State,
+        confirm: &str,
+        error: Option<String>,
+        encrypting: bool,
+    ) -> Element<'a, Message> {
+        let password_input =
+            TextInput::new(password_input, "Password", password, Message::Password)
+                .password()
+                .padding(15)
+                .size(30);
+        let confirm_input = TextInput::new(
+            confirm_input,
+            "Confirm password",
+            confirm,
+            Message::ConfirmPassword,
+        )
+        .password()
+        .padding(15)
+        .size(30);
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Password").size(50))
+            .push(Text::new(
+                "Your private keys are encrypted with this password. You'll need it to send.",
+            ))
+            .push(password_input)
+            .push(confirm_input);
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        if encrypting {
+            content = content.push(Text::new("Encrypting..."));
+        }
+        content.into()
+    }
+    fn signers(
+        signers: &[Signer],
+        accounts: &[Account],
+        add_buttons: [&'a mut button::State; 4],
+        remove_buttons: &'a mut [button::State],
+        threshold_input: &'a mut text_input::State,
+        threshold: &str,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let list = signers.iter().zip(remove_buttons).enumerate().fold(
+            Column::new().spacing(10),
+            |list, (index, (signer, remove_button))| {
+                // Point out keys that also guard other accounts
+                let shared: Vec<&str> = accounts
+                    .iter()
+                    .filter(|account| account.has_signer(signer))
+                    .map(|account| account.name.as_str())
+                    .collect();
+                let mut description = format!("{} ({})", signer.fingerprint, signer.label);
+                if !shared.is_empty() {
+                    description.push_str(&format!(", also in {}", shared.join(", ")));
+                }
+                list.push(
+                    Row::new()
+                        .spacing(10)
+                        .align_items(Align::Center)
+                        .push(Text::new(description))
+                        .push(
+                            button(remove_button, "Remove").on_press(Message::RemoveSigner(index)),
+                        ),
+                )
+            },
+        );
+
+        let [generate_button, restore_button, import_button, account_button] = add_buttons;
+        let add = Row::new()
+            .spacing(10)
+            .push(
+                button(generate_button, "New seed").on_press(Message::AddSigner(Method::Generate)),
+            )
+            .push(
+                button(restore_button, "Restore seed")
+                    .on_press(Message::AddSigner(Method::Restore)),
+            )
+            .push(
+                button(import_button, "Import xpub")
+                    .on_press(Message::AddSigner(Method::WatchOnly)),
+            )
+            .push(button(account_button, "From account").on_press(Message::AddAccountSigner));
+
+        let threshold = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(
+                TextInput::new(threshold_input, "M", threshold, Message::Threshold)
+                    .padding(15)
+                    .width(Length::Units(80)),
+            )
+            .push(Text::new(format!(
+                "of {} signatures required",
+                signers.len()
+            )));
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Signers").size(50))
+            .push(list)
+            .push(add)
+            .push(threshold);
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        content.into()
+    }
+    fn account_signer(
+        accounts: &[Account],
+        network: Option<Network>,
+        selected: Option<usize>,
+        password_input: &'a mut text_input::State,
+        password: &str,
+        error: Option<String>,
+        decrypting: bool,
+    ) -> Element<'a, Message> {
+        // Keys from other networks would make an unusable descriptor
+        let choices = accounts
+            .iter()
+            .enumerate()
+            // Nothing says which key of a multisig account is ours
+            .filter(|(_, account)| Some(account.network) == network && !account.is_multisig())
+            .fold(
+                Column::new().padding(10).spacing(20),
+                |choices, (index, account)| {
+                    choices.push(Radio::new(
+                        index,
+                        account.name.clone(),
+                        selected,
+                        Message::SignerAccountSelected,
+                    ))
+                },
+            );
+
+        let mut content = Column::new()
+            .padding(20)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("From Account").size(50))
+            .push(choices);
+        if let Some(account) = selected.map(|index| &accounts[index]) {
+            if account.is_encrypted() {
+                content = content.push(
+                    TextInput::new(
+                        password_input,
+                        "Account password",
+                        password,
+                        Message::SignerPassword,
+                    )
+                    .password()
+                    .padding(15)
+                    .size(30),
+                );
+            }
+        }
+        if let Some(error) = error {
+            content = content.push(Text::new(error));
+        }
+        if decrypting {
+            content = content.push(Text::new("Decrypting..."));
+        }
+        content.into()
+    }
 }
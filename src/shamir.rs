@@ -0,0 +1,578 @@
+//! SLIP-39 Shamir backups: a secret is split into groups of mnemonic shares, and any
+//! `group_threshold` groups with enough of their own shares recover it.
+//! https://github.com/satoshilabs/slips/blob/master/slip-0039.md
+
+use bitcoin::util::bip158::{BitStreamReader, BitStreamWriter};
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::sha2::Sha256;
+use once_cell::sync::Lazy;
+use rand::{thread_rng, Rng, RngCore};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use crate::error::Error;
+use crate::utils::normalize;
+
+/// The official SLIP-39 list. Sorted, and every word is unique in its first four letters.
+static WORDLIST: Lazy<Vec<&'static str>> =
+    Lazy::new(|| include_str!("wordlists/slip39.txt").lines().collect());
+
+const RADIX_BITS: usize = 10;
+/// Identifier, flags and group/member parameters come before the share value
+const PREFIX_WORDS: usize = 4;
+const CHECKSUM_WORDS: usize = 3;
+/// Shortest share, for a 128-bit secret
+const MIN_WORDS: usize = 20;
+const MIN_SECRET_BYTES: usize = 16;
+/// At most 16 groups, and 16 shares in each
+pub const MAX_SHARES: u8 = 16;
+
+const DIGEST_LENGTH: usize = 4;
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+
+const ROUND_COUNT: u32 = 4;
+const BASE_ITERATIONS: u32 = 10000;
+/// 20000 PBKDF2 iterations in total, like Trezor
+const ITERATION_EXPONENT: u8 = 1;
+
+/// Generator of the RS1024 checksum over GF(1024)
+const GENERATOR: [u32; 10] = [
+    0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009, 0x1c0c2412, 0x38086c24, 0x3090fc48,
+    0x21b1f890, 0x3f3f120,
+];
+
+/// One decoded share
+#[derive(Debug, Clone, PartialEq)]
+pub struct Share {
+    identifier: u16,
+    /// Extendable backups can gain groups later, and leave the identifier out of the
+    /// encryption salt
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Share {
+    fn mnemonic(&self) -> String {
+        let value_words = (self.value.len() * 8 + RADIX_BITS - 1) / RADIX_BITS;
+        let padding = value_words * RADIX_BITS - self.value.len() * 8;
+
+        let mut data = Vec::new();
+        let mut writer = BitStreamWriter::new(&mut data);
+        writer.write(self.identifier as u64, 15).unwrap();
+        writer.write(self.extendable as u64, 1).unwrap();
+        writer.write(self.iteration_exponent as u64, 4).unwrap();
+        writer.write(self.group_index as u64, 4).unwrap();
+        writer.write(self.group_threshold as u64 - 1, 4).unwrap();
+        writer.write(self.group_count as u64 - 1, 4).unwrap();
+        writer.write(self.member_index as u64, 4).unwrap();
+        writer.write(self.member_threshold as u64 - 1, 4).unwrap();
+        writer.write(0, padding as u8).unwrap();
+        for byte in &self.value {
+            writer.write(*byte as u64, 8).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let mut cursor = Cursor::new(&data);
+        let mut reader = BitStreamReader::new(&mut cursor);
+        let mut indexes: Vec<u32> = (0..PREFIX_WORDS + value_words)
+            .map(|_| reader.read(RADIX_BITS as u8).unwrap() as u32)
+            .collect();
+        let checksum = checksum(customization(self.extendable), &indexes);
+        indexes.extend_from_slice(&checksum);
+        indexes
+            .iter()
+            .map(|&index| WORDLIST[index as usize])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Exponent and logarithm tables of GF(256) with the Rijndael polynomial x^8 + x^4 + x^3 + x + 1
+static TABLES: Lazy<([u8; 255], [u8; 256])> = Lazy::new(|| {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut poly: u16 = 1;
+    for (i, power) in exp.iter_mut().enumerate() {
+        *power = poly as u8;
+        log[poly as usize] = i as u8;
+        // Multiply by the generator x + 1
+        poly ^= poly << 1;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11b;
+        }
+    }
+    (exp, log)
+});
+
+/// Value at `x` of the polynomials through `shares`, byte by byte
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Vec<u8> {
+    if let Some((_, value)) = shares.iter().find(|(index, _)| *index == x) {
+        return value.clone();
+    }
+    let (exp, logs) = &*TABLES;
+    let log = |a: u8| logs[a as usize] as u32;
+    let log_product: u32 = shares.iter().map(|&(index, _)| log(index ^ x)).sum();
+
+    let mut result = vec![0u8; shares[0].1.len()];
+    for (index, value) in shares {
+        let others: u32 = shares
+            .iter()
+            .filter(|(other, _)| other != index)
+            .map(|&(other, _)| log(index ^ other))
+            .sum();
+        // Adding 255 per share keeps the subtraction positive
+        let log_basis = (log_product + 255 * shares.len() as u32 - log(index ^ x) - others) % 255;
+        for (out, &byte) in result.iter_mut().zip(value) {
+            if byte != 0 {
+                *out ^= exp[((log(byte) + log_basis) % 255) as usize];
+            }
+        }
+    }
+    result
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::new(Sha256::new(), random);
+    mac.input(secret);
+    mac.result().code()[..DIGEST_LENGTH].to_vec()
+}
+
+/// `count` shares of `secret`, any `threshold` of which recover it
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    if threshold == 1 {
+        return (0..count).map(|index| (index, secret.to_vec())).collect();
+    }
+    let mut rng = thread_rng();
+    let random_count = threshold - 2;
+    let mut shares: Vec<(u8, Vec<u8>)> = (0..random_count)
+        .map(|index| {
+            let mut value = vec![0u8; secret.len()];
+            rng.fill_bytes(&mut value);
+            (index, value)
+        })
+        .collect();
+
+    // The digest lets recovery tell a wrong combination of shares from the real secret
+    let mut random = vec![0u8; secret.len() - DIGEST_LENGTH];
+    rng.fill_bytes(&mut random);
+    let mut digest_share = digest(&random, secret);
+    digest_share.extend(random);
+
+    let mut base = shares.clone();
+    base.push((DIGEST_INDEX, digest_share));
+    base.push((SECRET_INDEX, secret.to_vec()));
+    for index in random_count..count {
+        shares.push((index, interpolate(&base, index)));
+    }
+    shares
+}
+
+/// Inverse of `split_secret`, given exactly `threshold` shares
+fn recover_secret(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, Error> {
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest_share = interpolate(shares, DIGEST_INDEX);
+    if digest_share[..DIGEST_LENGTH] != digest(&digest_share[DIGEST_LENGTH..], &secret)[..] {
+        return Err(Error::Shamir(
+            "These shares don't belong together".to_string(),
+        ));
+    }
+    Ok(secret)
+}
+
+fn salt(identifier: u16, extendable: bool) -> Vec<u8> {
+    if extendable {
+        return vec![];
+    }
+    let mut salt = b"shamir".to_vec();
+    salt.extend_from_slice(&identifier.to_be_bytes());
+    salt
+}
+
+/// Four-round Feistel network keyed by the passphrase. Encrypting runs the rounds forwards,
+/// decrypting backwards.
+fn feistel(
+    data: &[u8],
+    passphrase: &str,
+    exponent: u8,
+    salt: &[u8],
+    rounds: impl Iterator<Item = u32>,
+) -> Vec<u8> {
+    let half = data.len() / 2;
+    let (mut left, mut right) = (data[..half].to_vec(), data[half..].to_vec());
+    for round in rounds {
+        let mut password = vec![round as u8];
+        password.extend_from_slice(passphrase.as_bytes());
+        let mut mac = Hmac::new(Sha256::new(), &password);
+        let mut round_salt = salt.to_vec();
+        round_salt.extend_from_slice(&right);
+        let mut key = vec![0u8; right.len()];
+        pbkdf2(
+            &mut mac,
+            &round_salt,
+            (BASE_ITERATIONS << exponent) / ROUND_COUNT,
+            &mut key,
+        );
+
+        let mixed = left.iter().zip(key).map(|(a, b)| a ^ b).collect();
+        left = std::mem::replace(&mut right, mixed);
+    }
+    right.extend(left);
+    right
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    if extendable {
+        b"shamir_extendable"
+    } else {
+        b"shamir"
+    }
+}
+
+fn polymod(customization: &[u8], values: &[u32]) -> u32 {
+    let mut checksum = 1;
+    for value in customization
+        .iter()
+        .map(|&c| c as u32)
+        .chain(values.iter().cloned())
+    {
+        let top = checksum >> 20;
+        checksum = ((checksum & 0xfffff) << 10) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// The three RS1024 checksum words for `data`
+fn checksum(customization: &[u8], data: &[u32]) -> [u32; 3] {
+    let mut values = data.to_vec();
+    values.extend_from_slice(&[0; CHECKSUM_WORDS]);
+    let polymod = polymod(customization, &values) ^ 1;
+    [
+        (polymod >> 20) & 1023,
+        (polymod >> 10) & 1023,
+        polymod & 1023,
+    ]
+}
+
+/// Split `secret` into SLIP-39 shares. `groups` holds each group's (member threshold, member
+/// count), and any `group_threshold` of the groups recover the secret. Returns the shares of
+/// each group, as space-separated words.
+pub fn split(
+    secret: &[u8],
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+) -> Result<Vec<Vec<String>>, Error> {
+    if secret.len() < MIN_SECRET_BYTES || secret.len() % 2 != 0 {
+        return Err(Error::Shamir(format!(
+            "Secret must be an even number of bytes, at least {}",
+            MIN_SECRET_BYTES
+        )));
+    }
+    if groups.is_empty() || groups.len() > MAX_SHARES as usize {
+        return Err(Error::Shamir(format!(
+            "Need between 1 and {} groups",
+            MAX_SHARES
+        )));
+    }
+    if group_threshold == 0 || group_threshold as usize > groups.len() {
+        return Err(Error::Shamir(
+            "Groups needed must be between 1 and the number of groups".to_string(),
+        ));
+    }
+    for (number, &(threshold, count)) in groups.iter().enumerate() {
+        if threshold == 0 || threshold > count || count > MAX_SHARES {
+            return Err(Error::Shamir(format!(
+                "Group {} needs between 1 and {} shares, and can't need more than it has",
+                number + 1,
+                MAX_SHARES
+            )));
+        }
+        // Extra copies of the same share would add risk without adding safety
+        if threshold == 1 && count > 1 {
+            return Err(Error::Shamir(format!(
+                "Group {} needs only one share, so it can't have more. Use more groups instead.",
+                number + 1
+            )));
+        }
+    }
+
+    let identifier = thread_rng().gen::<u16>() & 0x7fff;
+    let encrypted = feistel(
+        secret,
+        passphrase,
+        ITERATION_EXPONENT,
+        &salt(identifier, false),
+        0..ROUND_COUNT,
+    );
+    let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted);
+    Ok(groups
+        .iter()
+        .zip(group_secrets)
+        .map(|(&(threshold, count), (group_index, group_secret))| {
+            split_secret(threshold, count, &group_secret)
+                .into_iter()
+                .map(|(member_index, value)| {
+                    Share {
+                        identifier,
+                        extendable: false,
+                        iteration_exponent: ITERATION_EXPONENT,
+                        group_index,
+                        group_threshold,
+                        group_count: groups.len() as u8,
+                        member_index,
+                        member_threshold: threshold,
+                        value,
+                    }
+                    .mnemonic()
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Parse one share, checking every word is in the list and the checksum matches
+pub fn decode(share: &str) -> Result<Share, Error> {
+    let words: Vec<String> = share.split_whitespace().map(normalize).collect();
+    if words.len() < MIN_WORDS {
+        return Err(Error::Mnemonic(format!(
+            "Expected at least {} words but got {}",
+            MIN_WORDS,
+            words.len()
+        )));
+    }
+    let indexes = words
+        .iter()
+        .enumerate()
+        .map(|(position, word)| {
+            WORDLIST
+                .binary_search(&word.as_str())
+                .map(|index| index as u32)
+                .map_err(|_| Error::InvalidWord(position))
+        })
+        .collect::<Result<Vec<u32>, Error>>()?;
+
+    // The flag after the 15-bit identifier picks the checksum customization
+    let extendable = (indexes[1] >> 4) & 1 == 1;
+    if polymod(customization(extendable), &indexes) != 1 {
+        return Err(Error::InvalidChecksum);
+    }
+
+    let value_words = words.len() - PREFIX_WORDS - CHECKSUM_WORDS;
+    let value_bytes = value_words * RADIX_BITS / 16 * 2;
+    let padding = value_words * RADIX_BITS - value_bytes * 8;
+    if padding > 8 {
+        return Err(Error::Mnemonic("Wrong number of words".to_string()));
+    }
+
+    let mut data = Vec::new();
+    let mut writer = BitStreamWriter::new(&mut data);
+    for index in &indexes[..indexes.len() - CHECKSUM_WORDS] {
+        writer.write(*index as u64, RADIX_BITS as u8).unwrap();
+    }
+    writer.flush().unwrap();
+    let mut cursor = Cursor::new(&data);
+    let mut reader = BitStreamReader::new(&mut cursor);
+    let mut read = |bits: u8| reader.read(bits).unwrap() as u8;
+
+    let identifier = ((read(8) as u16) << 7) | read(7) as u16;
+    // Extendable flag, already used for the checksum
+    read(1);
+    let iteration_exponent = read(4);
+    let group_index = read(4);
+    let group_threshold = read(4) + 1;
+    let group_count = read(4) + 1;
+    let member_index = read(4);
+    let member_threshold = read(4) + 1;
+    if read(padding as u8) != 0 {
+        return Err(Error::Mnemonic("Padding isn't zero".to_string()));
+    }
+    let value = (0..value_bytes).map(|_| read(8)).collect();
+    if group_threshold > group_count {
+        return Err(Error::Mnemonic(
+            "Needs more groups than there are".to_string(),
+        ));
+    }
+
+    Ok(Share {
+        identifier,
+        extendable,
+        iteration_exponent,
+        group_index,
+        group_threshold,
+        group_count,
+        member_index,
+        member_threshold,
+        value,
+    })
+}
+
+/// Recover the secret from enough shares of enough groups. Extra shares are ignored.
+pub fn combine(shares: &[Share], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let first = shares
+        .first()
+        .ok_or_else(|| Error::Shamir("No shares entered".to_string()))?;
+    let same_backup = shares.iter().all(|share| {
+        share.identifier == first.identifier
+            && share.extendable == first.extendable
+            && share.iteration_exponent == first.iteration_exponent
+            && share.group_threshold == first.group_threshold
+            && share.group_count == first.group_count
+            && share.value.len() == first.value.len()
+    });
+    if !same_backup {
+        return Err(Error::Shamir(
+            "These shares are from different backups".to_string(),
+        ));
+    }
+
+    let mut groups: BTreeMap<u8, Vec<&Share>> = BTreeMap::new();
+    for share in shares {
+        let members = groups.entry(share.group_index).or_default();
+        if let Some(other) = members
+            .iter()
+            .find(|other| other.member_index == share.member_index)
+        {
+            if other.value != share.value {
+                return Err(Error::Shamir(format!(
+                    "Two different shares claim to be share {} of group {}",
+                    share.member_index + 1,
+                    share.group_index + 1
+                )));
+            }
+            continue;
+        }
+        if members
+            .iter()
+            .any(|other| other.member_threshold != share.member_threshold)
+        {
+            return Err(Error::Shamir(format!(
+                "Shares of group {} disagree on how many are needed",
+                share.group_index + 1
+            )));
+        }
+        members.push(share);
+    }
+
+    let mut group_secrets = Vec::new();
+    for (&group_index, members) in &groups {
+        let threshold = members[0].member_threshold;
+        if members.len() < threshold as usize {
+            continue;
+        }
+        let points: Vec<(u8, Vec<u8>)> = members
+            .iter()
+            .take(threshold as usize)
+            .map(|member| (member.member_index, member.value.clone()))
+            .collect();
+        group_secrets.push((group_index, recover_secret(threshold, &points)?));
+    }
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err(Error::Shamir(format!(
+            "Need enough shares from {} groups, but only {} are complete",
+            first.group_threshold,
+            group_secrets.len()
+        )));
+    }
+
+    let encrypted = recover_secret(
+        first.group_threshold,
+        &group_secrets[..first.group_threshold as usize],
+    )?;
+    Ok(feistel(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        &salt(first.identifier, first.extendable),
+        (0..ROUND_COUNT).rev(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::ToHex;
+
+    fn recover(shares: &[&str]) -> Result<Vec<u8>, Error> {
+        let shares = shares
+            .iter()
+            .map(|share| decode(share))
+            .collect::<Result<Vec<_>, _>>()?;
+        combine(&shares, "TREZOR")
+    }
+
+    // From https://github.com/trezor/python-shamir-mnemonic/blob/master/vectors.json
+    #[test]
+    fn slip39_vectors() {
+        assert_eq!(
+            recover(&["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard"])
+                .unwrap()
+                .to_hex(),
+            "bb54aac4b89dc868ba37d9cc21b2cece"
+        );
+        assert_eq!(
+            recover(&[
+                "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+                "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+            ])
+            .unwrap()
+            .to_hex(),
+            "b43ceb7e57a0ea8766221624d01b0864"
+        );
+        assert!(matches!(
+            recover(&["duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney"]),
+            Err(Error::InvalidChecksum)
+        ));
+    }
+
+    #[test]
+    fn wordlist_is_complete() {
+        assert_eq!(WORDLIST.len(), 1024);
+        assert!(WORDLIST.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn split_and_combine() {
+        let secret = [7u8; 16];
+        // Either both 2-of-3 groups, or the single-share group with one of them
+        let groups = split(&secret, "", 2, &[(2, 3), (2, 3), (1, 1)]).unwrap();
+        assert_eq!(groups[0].len(), 3);
+        let decode_all = |shares: &[&String]| -> Vec<Share> {
+            shares.iter().map(|share| decode(share).unwrap()).collect()
+        };
+
+        let enough = decode_all(&[&groups[0][2], &groups[0][0], &groups[2][0]]);
+        assert_eq!(combine(&enough, "").unwrap(), secret);
+        let enough = decode_all(&[&groups[0][1], &groups[1][0], &groups[0][2], &groups[1][2]]);
+        assert_eq!(combine(&enough, "").unwrap(), secret);
+
+        let short = decode_all(&[&groups[0][1], &groups[1][0], &groups[0][2]]);
+        assert!(matches!(combine(&short, ""), Err(Error::Shamir(_))));
+        assert_ne!(combine(&enough, "wrong").unwrap(), secret);
+    }
+
+    #[test]
+    fn split_rejects_bad_groups() {
+        let secret = [7u8; 16];
+        assert!(split(&secret, "", 2, &[(2, 3)]).is_err());
+        assert!(split(&secret, "", 1, &[(4, 3)]).is_err());
+        assert!(split(&secret, "", 1, &[(1, 2)]).is_err());
+        assert!(split(&secret[..15], "", 1, &[(2, 3)]).is_err());
+    }
+}
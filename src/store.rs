@@ -0,0 +1,138 @@
+use std::fs;
+
+use bitcoin::Network;
+use log::error;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::config;
+use crate::data::Account;
+use crate::error::Error;
+use crate::utils::change_descriptor;
+use crate::wallets::WALLETS;
+
+/// Load every saved account. A missing registry just means no accounts have been created yet.
+pub fn load_accounts() -> Result<Vec<Account>, Error> {
+    let path = config::get().accounts_path();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(path)?;
+    let mut records: Vec<Value> = serde_json::from_str(&contents)?;
+
+    // Accounts saved before they had an id kept their wallet tree under their name
+    let mut migrated = false;
+    let mut stale_trees = vec![];
+    for record in records.iter_mut() {
+        if record.get("id").is_none() {
+            let id = Uuid::new_v4();
+            let name = record["name"].as_str().unwrap_or_default().to_string();
+            let network: Network = serde_json::from_value(record["network"].clone())?;
+            copy_tree(network, &name, &id.to_string())?;
+            record["id"] = serde_json::to_value(id)?;
+            stale_trees.push((network, name));
+            migrated = true;
+        }
+        if add_change_descriptor(record) {
+            migrated = true;
+        }
+    }
+
+    let accounts: Vec<Account> = records
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()?;
+    if migrated {
+        save_accounts(&accounts)?;
+    }
+    // Only once the new ids are saved, so a failed save retries from the old trees next start
+    for (network, name) in stale_trees {
+        if let Err(e) = drop_tree(network, &name) {
+            error!("Couldn't drop migrated wallet tree {}: {:?}", name, e);
+        }
+    }
+    Ok(accounts)
+}
+
+/// Accounts saved before change descriptors sent change back to receive addresses, and may
+/// have saved the missing descriptor as `null`. True if one could be derived and was added.
+fn add_change_descriptor(record: &mut Value) -> bool {
+    let missing = record
+        .get("change_descriptor")
+        .map_or(true, |change| change.is_null());
+    if !missing {
+        return false;
+    }
+    match record["descriptor"].as_str().and_then(change_descriptor) {
+        Some(change) => {
+            record["change_descriptor"] = Value::String(change);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Overwrite the registry with `accounts`
+pub fn save_accounts(accounts: &[Account]) -> Result<(), Error> {
+    let config = config::get();
+    fs::create_dir_all(&config.datadir)?;
+
+    let path = config.accounts_path();
+    let contents = serde_json::to_string_pretty(accounts)?;
+    // Write to a temporary file first so a crash can't leave a half-written registry behind
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Sled can't rename trees, so copy every entry across
+fn copy_tree(network: Network, from: &str, to: &str) -> Result<(), Error> {
+    let db = WALLETS.database(network)?;
+    let old = db.open_tree(from)?;
+    let new = db.open_tree(to)?;
+    for entry in old.iter() {
+        let (key, value) = entry?;
+        new.insert(key, value)?;
+    }
+    new.flush()?;
+    Ok(())
+}
+
+fn drop_tree(network: Network, name: &str) -> Result<(), Error> {
+    WALLETS.database(network)?.drop_tree(name)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn change_descriptor_migration() {
+        let mut missing = json!({ "descriptor": "wpkh(xpubA/0/*)" });
+        assert!(add_change_descriptor(&mut missing));
+        assert_eq!(missing["change_descriptor"], "wpkh(xpubA/1/*)");
+
+        let mut null = json!({
+            "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "name": "Savings",
+            "descriptor": "wpkh(xpubA/0/*)",
+            "change_descriptor": null,
+            "network": "testnet",
+            "created_at": 0,
+        });
+        assert!(add_change_descriptor(&mut null));
+        let account: Account = serde_json::from_value(null).unwrap();
+        assert_eq!(account.change_descriptor.unwrap(), "wpkh(xpubA/1/*)");
+
+        // Already migrated, or nothing to derive from: leave the record alone
+        let mut saved = json!({ "descriptor": "wpkh(xpubA/0/*)", "change_descriptor": "custom" });
+        assert!(!add_change_descriptor(&mut saved));
+        assert_eq!(saved["change_descriptor"], "custom");
+        let mut underivable = json!({ "descriptor": "wpkh(xpubA)", "change_descriptor": null });
+        assert!(!add_change_descriptor(&mut underivable));
+        assert!(underivable["change_descriptor"].is_null());
+    }
+}
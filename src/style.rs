@@ -0,0 +1,16 @@
+use iced::{container, Background, Color};
+
+use crate::data::Theme;
+
+impl container::StyleSheet for Theme {
+    fn style(&self) -> container::Style {
+        match self {
+            Theme::Light => container::Style::default(),
+            Theme::Dark => container::Style {
+                text_color: Some(Color::from_rgb8(0xE0, 0xE0, 0xE0)),
+                background: Some(Background::Color(Color::from_rgb8(0x2B, 0x2D, 0x31))),
+                ..container::Style::default()
+            },
+        }
+    }
+}
@@ -1,37 +1,160 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
 use bitcoin::util::psbt::PartiallySignedTransaction;
-use bitcoin::{Address, Amount, Txid};
+use bitcoin::{Address, Amount, Network, OutPoint, Txid};
+use magical_bitcoin_wallet::electrum_client::ElectrumApi;
+use magical_bitcoin_wallet::types::{FeeRate, TransactionDetails};
 use magical_bitcoin_wallet::TxBuilder;
 
-use crate::data::Account;
+use crate::data::{Account, Coin, FeeSpeed, Signed, Signer};
 use crate::error::{consume_library_error, Error};
+use crate::utils;
+use crate::wallets::WALLETS;
+
+/// Lowest rate nodes relay, in sat/vB
+pub const MIN_FEE_RATE: f32 = 1.0;
 
 pub async fn update_account(account: Account) -> Result<Account, Error> {
     let mut clone = account.clone();
-    account.sync(None)?;
+    account.sync(Some(account.watched_addresses))?;
     clone.balance = Some(account.get_balance()?);
     clone.transactions = Some(account.list_transactions()?);
     Ok(clone)
 }
 
+pub async fn encrypt_account(
+    mut account: Account,
+    private_descriptor: String,
+    private_change_descriptor: Option<String>,
+    password: String,
+) -> Account {
+    account.encrypt(
+        &private_descriptor,
+        private_change_descriptor.as_deref(),
+        &password,
+    );
+    account
+}
+
+/// Write a backup sheet to `path`, never overwriting an existing file
+pub async fn save_backup_sheet(path: PathBuf, sheet: String) -> Result<PathBuf, Error> {
+    let result = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(sheet.as_bytes()));
+    match result {
+        Ok(()) => Ok(path),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(Error::File(
+            "A file with that name already exists".to_string(),
+        )),
+        Err(e) => Err(Error::File(e.to_string())),
+    }
+}
+
+/// Recipients from a CSV file, see `utils::parse_recipients`
+pub async fn read_recipients(path: PathBuf) -> Result<Vec<(String, String, String)>, Error> {
+    let csv = fs::read_to_string(&path).map_err(|e| Error::File(e.to_string()))?;
+    utils::parse_recipients(&csv)
+}
+
+/// Take the key out of an existing account to reuse as a multisig signer
+pub async fn account_signer(account: Account, password: String) -> Result<Signer, Error> {
+    let (descriptor, _) = account.private_descriptors(&password)?;
+    Signer::from_descriptor(&descriptor, account.name.clone())
+}
+
+/// Fee rates in sat/vB for each of `FeeSpeed::presets()`, from the server's `estimatefee`.
+/// None where the server has no estimate.
+pub async fn estimate_fees(network: Network) -> Result<Vec<Option<f32>>, Error> {
+    WALLETS.with_client(network, |client| {
+        FeeSpeed::presets()
+            .iter()
+            .map(|speed| {
+                let target = speed.target().expect("presets have a target");
+                // BTC/kB, or -1 when the server has no estimate, e.g. on regtest
+                let btc_per_kb = client.estimate_fee(target)?;
+                if btc_per_kb < 0.0 {
+                    return Ok(None);
+                }
+                Ok(Some(((btc_per_kb * 100_000.0) as f32).max(MIN_FEE_RATE)))
+            })
+            .collect()
+    })
+}
+
+/// The account's unspent outputs, largest first, with confirmations counted from the server's
+/// tip
+pub async fn list_coins(account: Account) -> Result<Vec<Coin>, Error> {
+    let tip = WALLETS.with_client(account.network, |client| {
+        Ok(client.block_headers_subscribe()?.height as u32)
+    })?;
+    let heights: HashMap<Txid, Option<u32>> = account
+        .list_transactions()?
+        .into_iter()
+        .map(|tx| (tx.txid, tx.height))
+        .collect();
+    let mut coins: Vec<Coin> = account
+        .list_unspent()?
+        .into_iter()
+        .map(|utxo| Coin {
+            confirmations: match heights.get(&utxo.outpoint.txid) {
+                Some(Some(height)) => tip.saturating_sub(*height) + 1,
+                _ => 0,
+            },
+            address: Address::from_script(&utxo.txout.script_pubkey, account.network),
+            amount: utxo.txout.value,
+            outpoint: utxo.outpoint,
+        })
+        .collect();
+    coins.sort_by(|a, b| b.amount.cmp(&a.amount));
+    Ok(coins)
+}
+
+/// Unsigned transaction paying every recipient at `fee_rate` sat/vB, with its fee. Any `coins`
+/// are always spent, alongside others as needed unless `only_coins`. With `send_all` the only
+/// recipient gets every coin spent, less the fee, whatever its amount.
 pub async fn create_psbt(
     account: Account,
-    address: Address,
-    amount: Amount,
-) -> Result<PartiallySignedTransaction, Error> {
-    let addressees = vec![(address, amount.as_sat())];
-    let builder = TxBuilder::from_addressees(addressees);
-    let psbt = account.get_wallet()?.create_tx(builder)?.0;
-    Ok(psbt)
+    recipients: Vec<(Address, Amount)>,
+    send_all: bool,
+    fee_rate: f32,
+    coins: Vec<OutPoint>,
+    only_coins: bool,
+) -> Result<(PartiallySignedTransaction, TransactionDetails), Error> {
+    let addressees = recipients
+        .into_iter()
+        .map(|(address, amount)| (address, amount.as_sat()))
+        .collect();
+    let mut builder =
+        TxBuilder::from_addressees(addressees).fee_rate(FeeRate::from_sat_per_vb(fee_rate));
+    if send_all {
+        builder = builder.send_all();
+    }
+    if !coins.is_empty() {
+        builder = builder.utxos(coins);
+        if only_coins {
+            builder = builder.manually_selected_only();
+        }
+    }
+    account.with_wallet(|wallet| Ok(wallet.create_tx(builder)?))
 }
 
 pub async fn sign_psbt(
     account: Account,
     psbt: PartiallySignedTransaction,
+    password: String,
 ) -> Result<(PartiallySignedTransaction, bool), Error> {
-    // FIXME: assume_height, assert that we have private key ???
+    // FIXME: assume_height
+    if account.is_watch_only() {
+        return Err(Error::WatchOnly);
+    }
     let assume_height = None;
     account
-        .get_wallet()?
+        .get_signing_wallet(&password)?
         .sign(psbt, assume_height)
         .map_err(consume_library_error)
 }
@@ -41,17 +164,29 @@ pub async fn broadcast_psbt(
     psbt: PartiallySignedTransaction,
 ) -> Result<Txid, Error> {
     let tx = psbt.extract_tx();
-    account
-        .get_wallet()?
-        .broadcast(tx)
-        .map_err(consume_library_error)
+    account.with_wallet(|wallet| wallet.broadcast(tx).map_err(Error::from))
+}
+
+fn signatures(psbt: &PartiallySignedTransaction) -> usize {
+    psbt.inputs
+        .iter()
+        .map(|input| input.partial_sigs.len())
+        .sum()
 }
 
-pub async fn send_money(account: Account, address: Address, amount: Amount) -> Result<Txid, Error> {
-    let psbt = create_psbt(account.clone(), address, amount).await?;
-    let (psbt, finalized) = sign_psbt(account.clone(), psbt).await?;
+/// Sign a transaction the user reviewed and broadcast it. Multisig accounts holding fewer keys
+/// than the threshold get the partially signed transaction back, for the other signers.
+pub async fn sign_and_broadcast(
+    account: Account,
+    psbt: PartiallySignedTransaction,
+    password: String,
+) -> Result<Signed, Error> {
+    let before = signatures(&psbt);
+    let (psbt, finalized) = sign_psbt(account.clone(), psbt, password).await?;
     if finalized {
-        broadcast_psbt(account, psbt).await
+        Ok(Signed::Broadcast(broadcast_psbt(account, psbt).await?))
+    } else if signatures(&psbt) > before {
+        Ok(Signed::Partially(psbt))
     } else {
         Err(Error::CouldNotFinalize)
     }
@@ -3,6 +3,7 @@ use iced::{
     Row, Text, TextInput,
 };
 
+use crate::config;
 use crate::data::Account;
 use crate::error::Error;
 use crate::tasks;
@@ -33,10 +34,11 @@ impl<'a> Page {
     pub fn view(&mut self) -> Element<Message> {
         let mut view = Column::new();
 
+        let unit = config::get().unit;
         if let Some(transactions) = &self.account.transactions {
             for tx in transactions {
                 let delta: i64 = tx.received as i64 - tx.sent as i64;
-                let row = Text::new(format!("{} {}", delta, tx.txid));
+                let row = Text::new(format!("{} {}", unit.format(delta), tx.txid));
                 view = view.push(row);
             }
         }
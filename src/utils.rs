@@ -1,10 +1,22 @@
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::address::{Address, Payload};
+use bitcoin::util::base58;
 use bitcoin::util::bip158::{BitStreamReader, BitStreamWriter};
+use bitcoin::util::bip32::{
+    ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint,
+};
+use bitcoin::{Network, Transaction};
 use crypto::digest::Digest;
-use crypto::sha2::Sha256;
+use crypto::hmac::Hmac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::sha2::{Sha256, Sha512};
+use once_cell::sync::Lazy;
 use rand::{thread_rng, RngCore};
 use std::io::Cursor;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
 
-use crate::data::Entropy;
+use crate::data::{Entropy, EntropySource, Language};
 use crate::error::Error;
 
 pub fn generate_entropy(amount: Entropy) -> Vec<u8> {
@@ -18,6 +30,44 @@ pub fn generate_entropy(amount: Entropy) -> Vec<u8> {
     random
 }
 
+/// Normalize typed dice rolls (1-6) or coin flips (H/T) to one character per roll, ignoring
+/// whitespace and separators
+pub fn rolls(input: &str, source: EntropySource) -> Result<String, Error> {
+    // Positions count characters of the input as typed, separators included
+    input
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| !c.is_whitespace() && *c != ',')
+        .map(|(position, c)| match (source, c.to_ascii_uppercase()) {
+            (EntropySource::Dice, '1'..='6') => Ok(c),
+            (EntropySource::Coins, side @ 'H') | (EntropySource::Coins, side @ 'T') => Ok(side),
+            _ => Err(Error::InvalidRoll(position)),
+        })
+        .collect()
+}
+
+/// Bits of entropy in `rolls`, assuming fair dice or coins
+pub fn roll_bits(rolls: &str, source: EntropySource) -> u32 {
+    (rolls.len() as f64 * source.bits_per_roll()).floor() as u32
+}
+
+/// Seed entropy from user rolls: SHA256 of the rolls, cut to size. Mixing XORs in system
+/// randomness, so the result is at least as strong as either source alone.
+pub fn entropy_from_rolls(rolls: &str, amount: Entropy, mix: bool) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.input_str(rolls);
+    let mut hash = [0u8; 32];
+    hasher.result(&mut hash);
+
+    let mut data = hash[..amount.bits() as usize / 8].to_vec();
+    if mix {
+        for (byte, random) in data.iter_mut().zip(generate_entropy(amount)) {
+            *byte ^= random;
+        }
+    }
+    data
+}
+
 fn checksum(data: &[u8]) -> Vec<u8> {
     let mut hash = [0u8; 32];
     let mut checksum = Vec::new();
@@ -35,7 +85,39 @@ fn checksum(data: &[u8]) -> Vec<u8> {
     checksum
 }
 
-pub fn mnemonic(data: &[u8]) -> Result<Vec<String>, Error> {
+/// The official BIP39 lists, in `Language::all()` order
+static WORDLISTS: Lazy<Vec<Vec<&'static str>>> = Lazy::new(|| {
+    [
+        include_str!("wordlists/english.txt"),
+        include_str!("wordlists/japanese.txt"),
+        include_str!("wordlists/korean.txt"),
+        include_str!("wordlists/spanish.txt"),
+        include_str!("wordlists/chinese_simplified.txt"),
+        include_str!("wordlists/chinese_traditional.txt"),
+        include_str!("wordlists/french.txt"),
+        include_str!("wordlists/italian.txt"),
+        include_str!("wordlists/czech.txt"),
+    ]
+    .iter()
+    .map(|list| list.lines().collect())
+    .collect()
+});
+
+/// The 2048 words for `language`
+pub fn wordlist(language: Language) -> &'static [&'static str] {
+    let index = Language::all()
+        .iter()
+        .position(|&l| l == language)
+        .expect("every language has a list");
+    &WORDLISTS[index]
+}
+
+/// Compatibility form of `word`, so accents and kana match however they were typed
+pub fn normalize(word: &str) -> String {
+    word.nfkd().collect::<String>().to_lowercase()
+}
+
+pub fn mnemonic(data: &[u8], language: Language) -> Result<Vec<String>, Error> {
     if data.len() % 4 != 0 {
         return Err(Error::Mnemonic(
             "Data for mnemonic should have a length divisible by 4".to_string(),
@@ -49,221 +131,762 @@ pub fn mnemonic(data: &[u8]) -> Result<Vec<String>, Error> {
     let mut mnemonic = Vec::new();
     for _ in 0..mlen {
         let index = reader.read(11).unwrap() as usize;
-        mnemonic.push(WORDS[index].to_string());
+        mnemonic.push(wordlist(language)[index].to_string());
     }
     Ok(mnemonic)
 }
 
-const WORDS: [&str; 2048] = [
-    "abandon", "ability", "able", "about", "above", "absent", "absorb", "abstract", "absurd",
-    "abuse", "access", "accident", "account", "accuse", "achieve", "acid", "acoustic", "acquire",
-    "across", "act", "action", "actor", "actress", "actual", "adapt", "add", "addict", "address",
-    "adjust", "admit", "adult", "advance", "advice", "aerobic", "affair", "afford", "afraid",
-    "again", "age", "agent", "agree", "ahead", "aim", "air", "airport", "aisle", "alarm", "album",
-    "alcohol", "alert", "alien", "all", "alley", "allow", "almost", "alone", "alpha", "already",
-    "also", "alter", "always", "amateur", "amazing", "among", "amount", "amused", "analyst",
-    "anchor", "ancient", "anger", "angle", "angry", "animal", "ankle", "announce", "annual",
-    "another", "answer", "antenna", "antique", "anxiety", "any", "apart", "apology", "appear",
-    "apple", "approve", "april", "arch", "arctic", "area", "arena", "argue", "arm", "armed",
-    "armor", "army", "around", "arrange", "arrest", "arrive", "arrow", "art", "artefact", "artist",
-    "artwork", "ask", "aspect", "assault", "asset", "assist", "assume", "asthma", "athlete",
-    "atom", "attack", "attend", "attitude", "attract", "auction", "audit", "august", "aunt",
-    "author", "auto", "autumn", "average", "avocado", "avoid", "awake", "aware", "away", "awesome",
-    "awful", "awkward", "axis", "baby", "bachelor", "bacon", "badge", "bag", "balance", "balcony",
-    "ball", "bamboo", "banana", "banner", "bar", "barely", "bargain", "barrel", "base", "basic",
-    "basket", "battle", "beach", "bean", "beauty", "because", "become", "beef", "before", "begin",
-    "behave", "behind", "believe", "below", "belt", "bench", "benefit", "best", "betray", "better",
-    "between", "beyond", "bicycle", "bid", "bike", "bind", "biology", "bird", "birth", "bitter",
-    "black", "blade", "blame", "blanket", "blast", "bleak", "bless", "blind", "blood", "blossom",
-    "blouse", "blue", "blur", "blush", "board", "boat", "body", "boil", "bomb", "bone", "bonus",
-    "book", "boost", "border", "boring", "borrow", "boss", "bottom", "bounce", "box", "boy",
-    "bracket", "brain", "brand", "brass", "brave", "bread", "breeze", "brick", "bridge", "brief",
-    "bright", "bring", "brisk", "broccoli", "broken", "bronze", "broom", "brother", "brown",
-    "brush", "bubble", "buddy", "budget", "buffalo", "build", "bulb", "bulk", "bullet", "bundle",
-    "bunker", "burden", "burger", "burst", "bus", "business", "busy", "butter", "buyer", "buzz",
-    "cabbage", "cabin", "cable", "cactus", "cage", "cake", "call", "calm", "camera", "camp", "can",
-    "canal", "cancel", "candy", "cannon", "canoe", "canvas", "canyon", "capable", "capital",
-    "captain", "car", "carbon", "card", "cargo", "carpet", "carry", "cart", "case", "cash",
-    "casino", "castle", "casual", "cat", "catalog", "catch", "category", "cattle", "caught",
-    "cause", "caution", "cave", "ceiling", "celery", "cement", "census", "century", "cereal",
-    "certain", "chair", "chalk", "champion", "change", "chaos", "chapter", "charge", "chase",
-    "chat", "cheap", "check", "cheese", "chef", "cherry", "chest", "chicken", "chief", "child",
-    "chimney", "choice", "choose", "chronic", "chuckle", "chunk", "churn", "cigar", "cinnamon",
-    "circle", "citizen", "city", "civil", "claim", "clap", "clarify", "claw", "clay", "clean",
-    "clerk", "clever", "click", "client", "cliff", "climb", "clinic", "clip", "clock", "clog",
-    "close", "cloth", "cloud", "clown", "club", "clump", "cluster", "clutch", "coach", "coast",
-    "coconut", "code", "coffee", "coil", "coin", "collect", "color", "column", "combine", "come",
-    "comfort", "comic", "common", "company", "concert", "conduct", "confirm", "congress",
-    "connect", "consider", "control", "convince", "cook", "cool", "copper", "copy", "coral",
-    "core", "corn", "correct", "cost", "cotton", "couch", "country", "couple", "course", "cousin",
-    "cover", "coyote", "crack", "cradle", "craft", "cram", "crane", "crash", "crater", "crawl",
-    "crazy", "cream", "credit", "creek", "crew", "cricket", "crime", "crisp", "critic", "crop",
-    "cross", "crouch", "crowd", "crucial", "cruel", "cruise", "crumble", "crunch", "crush", "cry",
-    "crystal", "cube", "culture", "cup", "cupboard", "curious", "current", "curtain", "curve",
-    "cushion", "custom", "cute", "cycle", "dad", "damage", "damp", "dance", "danger", "daring",
-    "dash", "daughter", "dawn", "day", "deal", "debate", "debris", "decade", "december", "decide",
-    "decline", "decorate", "decrease", "deer", "defense", "define", "defy", "degree", "delay",
-    "deliver", "demand", "demise", "denial", "dentist", "deny", "depart", "depend", "deposit",
-    "depth", "deputy", "derive", "describe", "desert", "design", "desk", "despair", "destroy",
-    "detail", "detect", "develop", "device", "devote", "diagram", "dial", "diamond", "diary",
-    "dice", "diesel", "diet", "differ", "digital", "dignity", "dilemma", "dinner", "dinosaur",
-    "direct", "dirt", "disagree", "discover", "disease", "dish", "dismiss", "disorder", "display",
-    "distance", "divert", "divide", "divorce", "dizzy", "doctor", "document", "dog", "doll",
-    "dolphin", "domain", "donate", "donkey", "donor", "door", "dose", "double", "dove", "draft",
-    "dragon", "drama", "drastic", "draw", "dream", "dress", "drift", "drill", "drink", "drip",
-    "drive", "drop", "drum", "dry", "duck", "dumb", "dune", "during", "dust", "dutch", "duty",
-    "dwarf", "dynamic", "eager", "eagle", "early", "earn", "earth", "easily", "east", "easy",
-    "echo", "ecology", "economy", "edge", "edit", "educate", "effort", "egg", "eight", "either",
-    "elbow", "elder", "electric", "elegant", "element", "elephant", "elevator", "elite", "else",
-    "embark", "embody", "embrace", "emerge", "emotion", "employ", "empower", "empty", "enable",
-    "enact", "end", "endless", "endorse", "enemy", "energy", "enforce", "engage", "engine",
-    "enhance", "enjoy", "enlist", "enough", "enrich", "enroll", "ensure", "enter", "entire",
-    "entry", "envelope", "episode", "equal", "equip", "era", "erase", "erode", "erosion", "error",
-    "erupt", "escape", "essay", "essence", "estate", "eternal", "ethics", "evidence", "evil",
-    "evoke", "evolve", "exact", "example", "excess", "exchange", "excite", "exclude", "excuse",
-    "execute", "exercise", "exhaust", "exhibit", "exile", "exist", "exit", "exotic", "expand",
-    "expect", "expire", "explain", "expose", "express", "extend", "extra", "eye", "eyebrow",
-    "fabric", "face", "faculty", "fade", "faint", "faith", "fall", "false", "fame", "family",
-    "famous", "fan", "fancy", "fantasy", "farm", "fashion", "fat", "fatal", "father", "fatigue",
-    "fault", "favorite", "feature", "february", "federal", "fee", "feed", "feel", "female",
-    "fence", "festival", "fetch", "fever", "few", "fiber", "fiction", "field", "figure", "file",
-    "film", "filter", "final", "find", "fine", "finger", "finish", "fire", "firm", "first",
-    "fiscal", "fish", "fit", "fitness", "fix", "flag", "flame", "flash", "flat", "flavor", "flee",
-    "flight", "flip", "float", "flock", "floor", "flower", "fluid", "flush", "fly", "foam",
-    "focus", "fog", "foil", "fold", "follow", "food", "foot", "force", "forest", "forget", "fork",
-    "fortune", "forum", "forward", "fossil", "foster", "found", "fox", "fragile", "frame",
-    "frequent", "fresh", "friend", "fringe", "frog", "front", "frost", "frown", "frozen", "fruit",
-    "fuel", "fun", "funny", "furnace", "fury", "future", "gadget", "gain", "galaxy", "gallery",
-    "game", "gap", "garage", "garbage", "garden", "garlic", "garment", "gas", "gasp", "gate",
-    "gather", "gauge", "gaze", "general", "genius", "genre", "gentle", "genuine", "gesture",
-    "ghost", "giant", "gift", "giggle", "ginger", "giraffe", "girl", "give", "glad", "glance",
-    "glare", "glass", "glide", "glimpse", "globe", "gloom", "glory", "glove", "glow", "glue",
-    "goat", "goddess", "gold", "good", "goose", "gorilla", "gospel", "gossip", "govern", "gown",
-    "grab", "grace", "grain", "grant", "grape", "grass", "gravity", "great", "green", "grid",
-    "grief", "grit", "grocery", "group", "grow", "grunt", "guard", "guess", "guide", "guilt",
-    "guitar", "gun", "gym", "habit", "hair", "half", "hammer", "hamster", "hand", "happy",
-    "harbor", "hard", "harsh", "harvest", "hat", "have", "hawk", "hazard", "head", "health",
-    "heart", "heavy", "hedgehog", "height", "hello", "helmet", "help", "hen", "hero", "hidden",
-    "high", "hill", "hint", "hip", "hire", "history", "hobby", "hockey", "hold", "hole", "holiday",
-    "hollow", "home", "honey", "hood", "hope", "horn", "horror", "horse", "hospital", "host",
-    "hotel", "hour", "hover", "hub", "huge", "human", "humble", "humor", "hundred", "hungry",
-    "hunt", "hurdle", "hurry", "hurt", "husband", "hybrid", "ice", "icon", "idea", "identify",
-    "idle", "ignore", "ill", "illegal", "illness", "image", "imitate", "immense", "immune",
-    "impact", "impose", "improve", "impulse", "inch", "include", "income", "increase", "index",
-    "indicate", "indoor", "industry", "infant", "inflict", "inform", "inhale", "inherit",
-    "initial", "inject", "injury", "inmate", "inner", "innocent", "input", "inquiry", "insane",
-    "insect", "inside", "inspire", "install", "intact", "interest", "into", "invest", "invite",
-    "involve", "iron", "island", "isolate", "issue", "item", "ivory", "jacket", "jaguar", "jar",
-    "jazz", "jealous", "jeans", "jelly", "jewel", "job", "join", "joke", "journey", "joy", "judge",
-    "juice", "jump", "jungle", "junior", "junk", "just", "kangaroo", "keen", "keep", "ketchup",
-    "key", "kick", "kid", "kidney", "kind", "kingdom", "kiss", "kit", "kitchen", "kite", "kitten",
-    "kiwi", "knee", "knife", "knock", "know", "lab", "label", "labor", "ladder", "lady", "lake",
-    "lamp", "language", "laptop", "large", "later", "latin", "laugh", "laundry", "lava", "law",
-    "lawn", "lawsuit", "layer", "lazy", "leader", "leaf", "learn", "leave", "lecture", "left",
-    "leg", "legal", "legend", "leisure", "lemon", "lend", "length", "lens", "leopard", "lesson",
-    "letter", "level", "liar", "liberty", "library", "license", "life", "lift", "light", "like",
-    "limb", "limit", "link", "lion", "liquid", "list", "little", "live", "lizard", "load", "loan",
-    "lobster", "local", "lock", "logic", "lonely", "long", "loop", "lottery", "loud", "lounge",
-    "love", "loyal", "lucky", "luggage", "lumber", "lunar", "lunch", "luxury", "lyrics", "machine",
-    "mad", "magic", "magnet", "maid", "mail", "main", "major", "make", "mammal", "man", "manage",
-    "mandate", "mango", "mansion", "manual", "maple", "marble", "march", "margin", "marine",
-    "market", "marriage", "mask", "mass", "master", "match", "material", "math", "matrix",
-    "matter", "maximum", "maze", "meadow", "mean", "measure", "meat", "mechanic", "medal", "media",
-    "melody", "melt", "member", "memory", "mention", "menu", "mercy", "merge", "merit", "merry",
-    "mesh", "message", "metal", "method", "middle", "midnight", "milk", "million", "mimic", "mind",
-    "minimum", "minor", "minute", "miracle", "mirror", "misery", "miss", "mistake", "mix", "mixed",
-    "mixture", "mobile", "model", "modify", "mom", "moment", "monitor", "monkey", "monster",
-    "month", "moon", "moral", "more", "morning", "mosquito", "mother", "motion", "motor",
-    "mountain", "mouse", "move", "movie", "much", "muffin", "mule", "multiply", "muscle", "museum",
-    "mushroom", "music", "must", "mutual", "myself", "mystery", "myth", "naive", "name", "napkin",
-    "narrow", "nasty", "nation", "nature", "near", "neck", "need", "negative", "neglect",
-    "neither", "nephew", "nerve", "nest", "net", "network", "neutral", "never", "news", "next",
-    "nice", "night", "noble", "noise", "nominee", "noodle", "normal", "north", "nose", "notable",
-    "note", "nothing", "notice", "novel", "now", "nuclear", "number", "nurse", "nut", "oak",
-    "obey", "object", "oblige", "obscure", "observe", "obtain", "obvious", "occur", "ocean",
-    "october", "odor", "off", "offer", "office", "often", "oil", "okay", "old", "olive", "olympic",
-    "omit", "once", "one", "onion", "online", "only", "open", "opera", "opinion", "oppose",
-    "option", "orange", "orbit", "orchard", "order", "ordinary", "organ", "orient", "original",
-    "orphan", "ostrich", "other", "outdoor", "outer", "output", "outside", "oval", "oven", "over",
-    "own", "owner", "oxygen", "oyster", "ozone", "pact", "paddle", "page", "pair", "palace",
-    "palm", "panda", "panel", "panic", "panther", "paper", "parade", "parent", "park", "parrot",
-    "party", "pass", "patch", "path", "patient", "patrol", "pattern", "pause", "pave", "payment",
-    "peace", "peanut", "pear", "peasant", "pelican", "pen", "penalty", "pencil", "people",
-    "pepper", "perfect", "permit", "person", "pet", "phone", "photo", "phrase", "physical",
-    "piano", "picnic", "picture", "piece", "pig", "pigeon", "pill", "pilot", "pink", "pioneer",
-    "pipe", "pistol", "pitch", "pizza", "place", "planet", "plastic", "plate", "play", "please",
-    "pledge", "pluck", "plug", "plunge", "poem", "poet", "point", "polar", "pole", "police",
-    "pond", "pony", "pool", "popular", "portion", "position", "possible", "post", "potato",
-    "pottery", "poverty", "powder", "power", "practice", "praise", "predict", "prefer", "prepare",
-    "present", "pretty", "prevent", "price", "pride", "primary", "print", "priority", "prison",
-    "private", "prize", "problem", "process", "produce", "profit", "program", "project", "promote",
-    "proof", "property", "prosper", "protect", "proud", "provide", "public", "pudding", "pull",
-    "pulp", "pulse", "pumpkin", "punch", "pupil", "puppy", "purchase", "purity", "purpose",
-    "purse", "push", "put", "puzzle", "pyramid", "quality", "quantum", "quarter", "question",
-    "quick", "quit", "quiz", "quote", "rabbit", "raccoon", "race", "rack", "radar", "radio",
-    "rail", "rain", "raise", "rally", "ramp", "ranch", "random", "range", "rapid", "rare", "rate",
-    "rather", "raven", "raw", "razor", "ready", "real", "reason", "rebel", "rebuild", "recall",
-    "receive", "recipe", "record", "recycle", "reduce", "reflect", "reform", "refuse", "region",
-    "regret", "regular", "reject", "relax", "release", "relief", "rely", "remain", "remember",
-    "remind", "remove", "render", "renew", "rent", "reopen", "repair", "repeat", "replace",
-    "report", "require", "rescue", "resemble", "resist", "resource", "response", "result",
-    "retire", "retreat", "return", "reunion", "reveal", "review", "reward", "rhythm", "rib",
-    "ribbon", "rice", "rich", "ride", "ridge", "rifle", "right", "rigid", "ring", "riot", "ripple",
-    "risk", "ritual", "rival", "river", "road", "roast", "robot", "robust", "rocket", "romance",
-    "roof", "rookie", "room", "rose", "rotate", "rough", "round", "route", "royal", "rubber",
-    "rude", "rug", "rule", "run", "runway", "rural", "sad", "saddle", "sadness", "safe", "sail",
-    "salad", "salmon", "salon", "salt", "salute", "same", "sample", "sand", "satisfy", "satoshi",
-    "sauce", "sausage", "save", "say", "scale", "scan", "scare", "scatter", "scene", "scheme",
-    "school", "science", "scissors", "scorpion", "scout", "scrap", "screen", "script", "scrub",
-    "sea", "search", "season", "seat", "second", "secret", "section", "security", "seed", "seek",
-    "segment", "select", "sell", "seminar", "senior", "sense", "sentence", "series", "service",
-    "session", "settle", "setup", "seven", "shadow", "shaft", "shallow", "share", "shed", "shell",
-    "sheriff", "shield", "shift", "shine", "ship", "shiver", "shock", "shoe", "shoot", "shop",
-    "short", "shoulder", "shove", "shrimp", "shrug", "shuffle", "shy", "sibling", "sick", "side",
-    "siege", "sight", "sign", "silent", "silk", "silly", "silver", "similar", "simple", "since",
-    "sing", "siren", "sister", "situate", "six", "size", "skate", "sketch", "ski", "skill", "skin",
-    "skirt", "skull", "slab", "slam", "sleep", "slender", "slice", "slide", "slight", "slim",
-    "slogan", "slot", "slow", "slush", "small", "smart", "smile", "smoke", "smooth", "snack",
-    "snake", "snap", "sniff", "snow", "soap", "soccer", "social", "sock", "soda", "soft", "solar",
-    "soldier", "solid", "solution", "solve", "someone", "song", "soon", "sorry", "sort", "soul",
-    "sound", "soup", "source", "south", "space", "spare", "spatial", "spawn", "speak", "special",
-    "speed", "spell", "spend", "sphere", "spice", "spider", "spike", "spin", "spirit", "split",
-    "spoil", "sponsor", "spoon", "sport", "spot", "spray", "spread", "spring", "spy", "square",
-    "squeeze", "squirrel", "stable", "stadium", "staff", "stage", "stairs", "stamp", "stand",
-    "start", "state", "stay", "steak", "steel", "stem", "step", "stereo", "stick", "still",
-    "sting", "stock", "stomach", "stone", "stool", "story", "stove", "strategy", "street",
-    "strike", "strong", "struggle", "student", "stuff", "stumble", "style", "subject", "submit",
-    "subway", "success", "such", "sudden", "suffer", "sugar", "suggest", "suit", "summer", "sun",
-    "sunny", "sunset", "super", "supply", "supreme", "sure", "surface", "surge", "surprise",
-    "surround", "survey", "suspect", "sustain", "swallow", "swamp", "swap", "swarm", "swear",
-    "sweet", "swift", "swim", "swing", "switch", "sword", "symbol", "symptom", "syrup", "system",
-    "table", "tackle", "tag", "tail", "talent", "talk", "tank", "tape", "target", "task", "taste",
-    "tattoo", "taxi", "teach", "team", "tell", "ten", "tenant", "tennis", "tent", "term", "test",
-    "text", "thank", "that", "theme", "then", "theory", "there", "they", "thing", "this",
-    "thought", "three", "thrive", "throw", "thumb", "thunder", "ticket", "tide", "tiger", "tilt",
-    "timber", "time", "tiny", "tip", "tired", "tissue", "title", "toast", "tobacco", "today",
-    "toddler", "toe", "together", "toilet", "token", "tomato", "tomorrow", "tone", "tongue",
-    "tonight", "tool", "tooth", "top", "topic", "topple", "torch", "tornado", "tortoise", "toss",
-    "total", "tourist", "toward", "tower", "town", "toy", "track", "trade", "traffic", "tragic",
-    "train", "transfer", "trap", "trash", "travel", "tray", "treat", "tree", "trend", "trial",
-    "tribe", "trick", "trigger", "trim", "trip", "trophy", "trouble", "truck", "true", "truly",
-    "trumpet", "trust", "truth", "try", "tube", "tuition", "tumble", "tuna", "tunnel", "turkey",
-    "turn", "turtle", "twelve", "twenty", "twice", "twin", "twist", "two", "type", "typical",
-    "ugly", "umbrella", "unable", "unaware", "uncle", "uncover", "under", "undo", "unfair",
-    "unfold", "unhappy", "uniform", "unique", "unit", "universe", "unknown", "unlock", "until",
-    "unusual", "unveil", "update", "upgrade", "uphold", "upon", "upper", "upset", "urban", "urge",
-    "usage", "use", "used", "useful", "useless", "usual", "utility", "vacant", "vacuum", "vague",
-    "valid", "valley", "valve", "van", "vanish", "vapor", "various", "vast", "vault", "vehicle",
-    "velvet", "vendor", "venture", "venue", "verb", "verify", "version", "very", "vessel",
-    "veteran", "viable", "vibrant", "vicious", "victory", "video", "view", "village", "vintage",
-    "violin", "virtual", "virus", "visa", "visit", "visual", "vital", "vivid", "vocal", "voice",
-    "void", "volcano", "volume", "vote", "voyage", "wage", "wagon", "wait", "walk", "wall",
-    "walnut", "want", "warfare", "warm", "warrior", "wash", "wasp", "waste", "water", "wave",
-    "way", "wealth", "weapon", "wear", "weasel", "weather", "web", "wedding", "weekend", "weird",
-    "welcome", "west", "wet", "whale", "what", "wheat", "wheel", "when", "where", "whip",
-    "whisper", "wide", "width", "wife", "wild", "will", "win", "window", "wine", "wing", "wink",
-    "winner", "winter", "wire", "wisdom", "wise", "wish", "witness", "wolf", "woman", "wonder",
-    "wood", "wool", "word", "work", "world", "worry", "worth", "wrap", "wreck", "wrestle", "wrist",
-    "write", "wrong", "yard", "year", "yellow", "you", "young", "youth", "zebra", "zero", "zone",
-    "zoo",
+/// Inverse of `mnemonic`: checks every word is in the list and the checksum matches
+pub fn entropy(words: &[String], language: Language) -> Result<Vec<u8>, Error> {
+    if ![12, 15, 18, 21, 24].contains(&words.len()) {
+        return Err(Error::Mnemonic(format!(
+            "Expected 12, 15, 18, 21 or 24 words but got {}",
+            words.len()
+        )));
+    }
+    let mut with_checksum = Vec::new();
+    let mut writer = BitStreamWriter::new(&mut with_checksum);
+    // Not every list is sorted, so search by normalized form
+    let list: Vec<String> = wordlist(language).iter().map(|w| normalize(w)).collect();
+    for (position, word) in words.iter().enumerate() {
+        let word = normalize(word);
+        let index = list
+            .iter()
+            .position(|w| *w == word)
+            .ok_or(Error::InvalidWord(position))?;
+        writer.write(index as u64, 11).unwrap();
+    }
+    writer.flush().unwrap();
+
+    // Every 3 words hold 4 bytes of entropy and 1 bit of checksum
+    let data = with_checksum[..words.len() * 4 / 3].to_vec();
+    if with_checksum[data.len()] != checksum(&data)[0] {
+        return Err(Error::InvalidChecksum);
+    }
+    Ok(data)
+}
+
+/// Up to `limit` words from the list starting with `prefix`
+pub fn suggestions(prefix: &str, limit: usize, language: Language) -> Vec<&'static str> {
+    if prefix.is_empty() {
+        return vec![];
+    }
+    let prefix = normalize(prefix);
+    wordlist(language)
+        .iter()
+        .filter(|word| normalize(word).starts_with(&prefix))
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+/// BIP39 mnemonic-to-seed: PBKDF2-HMAC-SHA512 over the space-separated words, salted with
+/// "mnemonic" + passphrase, both NFKD normalized
+pub fn seed(words: &[String], passphrase: &str) -> [u8; 64] {
+    let sentence: String = words.join(" ").nfkd().collect();
+    let salt: String = format!("mnemonic{}", passphrase).nfkd().collect();
+    let mut mac = Hmac::new(Sha512::new(), sentence.as_bytes());
+    let mut seed = [0u8; 64];
+    pbkdf2(&mut mac, salt.as_bytes(), 2048, &mut seed);
+    seed
+}
+
+/// BIP44-style account path `m/purpose'/coin'/account'`, with coin type 1 for every test network
+pub fn account_path(purpose: u32, network: Network, account: u32) -> DerivationPath {
+    let coin = match network {
+        Network::Bitcoin => 0,
+        _ => 1,
+    };
+    hardened_path(&[purpose, coin, account])
+}
+
+/// BIP48 path for native segwit multisig cosigners, `m/48'/coin'/account'/2'`
+pub fn multisig_path(network: Network, account: u32) -> DerivationPath {
+    let coin = match network {
+        Network::Bitcoin => 0,
+        _ => 1,
+    };
+    hardened_path(&[48, coin, account, 2])
+}
+
+fn hardened_path(indexes: &[u32]) -> DerivationPath {
+    let path: Vec<ChildNumber> = indexes
+        .iter()
+        .map(|&index| ChildNumber::from_hardened_idx(index).expect("index below 2^31"))
+        .collect();
+    DerivationPath::from(path)
+}
+
+/// `[fingerprint/path]` annotation telling other wallets where a key comes from
+pub fn key_origin(fingerprint: Fingerprint, path: &DerivationPath) -> String {
+    // Paths display as "m/84'/0'/0'", origins leave out the "m"
+    format!(
+        "[{}{}]",
+        fingerprint,
+        path.to_string().trim_start_matches('m')
+    )
+}
+
+/// Fingerprint and path of the first `[fingerprint/path]` key origin in `descriptor`
+pub fn parse_key_origin(descriptor: &str) -> Option<(Fingerprint, DerivationPath)> {
+    let start = descriptor.find('[')?;
+    let end = start + descriptor[start..].find(']')?;
+    let mut parts = descriptor[start + 1..end].splitn(2, '/');
+    let fingerprint = Fingerprint::from_str(parts.next()?).ok()?;
+    let path = match parts.next() {
+        Some(path) => DerivationPath::from_str(&format!("m/{}", path)).ok()?,
+        None => DerivationPath::from(vec![]),
+    };
+    Some((fingerprint, path))
+}
+
+/// Split an optional leading `[fingerprint/path]` key origin off a pasted key, as hardware
+/// wallets export it, so the key can be listed under its master fingerprint
+pub fn split_key_origin(
+    input: &str,
+) -> Result<(Option<(Fingerprint, DerivationPath)>, &str), Error> {
+    let input = input.trim();
+    if !input.starts_with('[') {
+        return Ok((None, input));
+    }
+    let end = input
+        .find(']')
+        .ok_or_else(|| Error::Import("Key origin is missing its closing ']'".to_string()))?;
+    let origin = parse_key_origin(&input[..=end]).ok_or_else(|| {
+        Error::Import("Key origin should look like [fingerprint/48'/0'/0'/2']".to_string())
+    })?;
+    Ok((Some(origin), input[end + 1..].trim()))
+}
+
+/// Replace every extended private key in `descriptor` with its extended public key. A checksum
+/// on the input is recomputed, since the old one no longer matches.
+pub fn public_descriptor(descriptor: &str) -> String {
+    let secp = Secp256k1::new();
+    let mut parts = descriptor.splitn(2, '#');
+    let mut public = parts.next().unwrap_or(descriptor).to_string();
+    let had_checksum = parts.next().is_some();
+    for token in descriptor.split(|c: char| "()[],/*'#".contains(c)) {
+        if let Ok(xprv) = ExtendedPrivKey::from_str(token) {
+            let xpub = ExtendedPubKey::from_private(&secp, &xprv);
+            public = public.replace(token, &xpub.to_string());
+        }
+    }
+    match with_checksum(&public) {
+        Ok(checksummed) if had_checksum => checksummed,
+        _ => public,
+    }
+}
+
+/// The change (`/1/*`) counterpart of a receive descriptor on the `/0/*` chain. Any checksum is
+/// dropped, since it wouldn't match anymore.
+pub fn change_descriptor(descriptor: &str) -> Option<String> {
+    let descriptor = descriptor.split('#').next().unwrap_or(descriptor);
+    if descriptor.contains("/0/*") {
+        Some(descriptor.replace("/0/*", "/1/*"))
+    } else {
+        None
+    }
+}
+
+/// Characters a descriptor may contain, in the order the BIP380 checksum groups them
+const DESCRIPTOR_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn descriptor_polymod(checksum: u64, value: u64) -> u64 {
+    const GENERATOR: [u64; 5] = [
+        0xf5dee51989,
+        0xa9fdca3312,
+        0x1bab10e32d,
+        0x3706b1677a,
+        0x644d626ffd,
+    ];
+    let top = checksum >> 35;
+    let mut checksum = ((checksum & 0x7ffffffff) << 5) ^ value;
+    for (i, generator) in GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            checksum ^= generator;
+        }
+    }
+    checksum
+}
+
+/// `descriptor` with its BIP380 checksum, replacing any it already had, so other wallets can
+/// tell it was copied correctly
+pub fn with_checksum(descriptor: &str) -> Result<String, Error> {
+    let descriptor = descriptor.split('#').next().unwrap_or(descriptor);
+    let mut checksum = 1;
+    let mut classes = 0;
+    let mut class_count = 0;
+    for c in descriptor.chars() {
+        let position = DESCRIPTOR_CHARSET
+            .find(c)
+            .ok_or_else(|| Error::Import(format!("Descriptors can't contain '{}'", c)))?
+            as u64;
+        checksum = descriptor_polymod(checksum, position & 31);
+        // Every 3 characters also feed in which group of 32 they came from
+        classes = classes * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            checksum = descriptor_polymod(checksum, classes);
+            classes = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        checksum = descriptor_polymod(checksum, classes);
+    }
+    for _ in 0..8 {
+        checksum = descriptor_polymod(checksum, 0);
+    }
+    checksum ^= 1;
+
+    let suffix: String = (0..8)
+        .map(|i| CHECKSUM_CHARSET[((checksum >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect();
+    Ok(format!("{}#{}", descriptor, suffix))
+}
+
+/// Fingerprint of the first key in `descriptor`: its key origin if it has one, otherwise the
+/// key's own fingerprint
+pub fn fingerprint(descriptor: &str) -> Option<Fingerprint> {
+    if let Some(start) = descriptor.find('[') {
+        let origin = &descriptor[start + 1..];
+        let end = origin.find(|c| c == '/' || c == ']')?;
+        return Fingerprint::from_str(&origin[..end]).ok();
+    }
+    let secp = Secp256k1::new();
+    for token in descriptor.split(|c: char| "()[],/*'#".contains(c)) {
+        if let Ok(xpub) = ExtendedPubKey::from_str(token) {
+            return Some(xpub.fingerprint());
+        }
+        if let Ok(xprv) = ExtendedPrivKey::from_str(token) {
+            return Some(xprv.fingerprint(&secp));
+        }
+    }
+    None
+}
+
+/// Fingerprints of every key in `descriptor`, including those in key origins
+pub fn fingerprints(descriptor: &str) -> Vec<Fingerprint> {
+    let secp = Secp256k1::new();
+    let mut fingerprints = vec![];
+    for (start, _) in descriptor.match_indices('[') {
+        let origin = &descriptor[start + 1..];
+        if let Some(end) = origin.find(|c| c == '/' || c == ']') {
+            if let Ok(fingerprint) = Fingerprint::from_str(&origin[..end]) {
+                fingerprints.push(fingerprint);
+            }
+        }
+    }
+    for token in descriptor.split(|c: char| "()[],/*'#".contains(c)) {
+        if let Ok(xpub) = ExtendedPubKey::from_str(token) {
+            fingerprints.push(xpub.fingerprint());
+        } else if let Ok(xprv) = ExtendedPrivKey::from_str(token) {
+            fingerprints.push(xprv.fingerprint(&secp));
+        }
+    }
+    fingerprints.dedup();
+    fingerprints
+}
+
+/// Master fingerprint of each key in `descriptor`, in order: from its key origin if it has one,
+/// otherwise the key's own
+pub fn key_fingerprints(descriptor: &str) -> Vec<Fingerprint> {
+    let secp = Secp256k1::new();
+    let descriptor = descriptor.split('#').next().unwrap_or(descriptor);
+    let mut fingerprints = vec![];
+    for key in descriptor.split(|c: char| "(),".contains(c)) {
+        if key.starts_with('[') {
+            if let Some((fingerprint, _)) = parse_key_origin(key) {
+                fingerprints.push(fingerprint);
+            }
+            continue;
+        }
+        for token in key.split(|c: char| "/*'".contains(c)) {
+            if let Ok(xpub) = ExtendedPubKey::from_str(token) {
+                fingerprints.push(xpub.fingerprint());
+            } else if let Ok(xprv) = ExtendedPrivKey::from_str(token) {
+                fingerprints.push(xprv.fingerprint(&secp));
+            }
+        }
+    }
+    fingerprints
+}
+
+/// Fail unless every extended key in `descriptor` belongs to `network`. Regtest keys use the
+/// testnet version bytes.
+pub fn check_network(descriptor: &str, network: Network) -> Result<(), Error> {
+    for token in descriptor.split(|c: char| "()[],/*'#".contains(c)) {
+        let key_network = if let Ok(xpub) = ExtendedPubKey::from_str(token) {
+            xpub.network
+        } else if let Ok(xprv) = ExtendedPrivKey::from_str(token) {
+            xprv.network
+        } else {
+            continue;
+        };
+        if (key_network == Network::Bitcoin) != (network == Network::Bitcoin) {
+            return Err(Error::Import(match network {
+                Network::Bitcoin => "Key is for a test network, not mainnet".to_string(),
+                _ => format!("Key is for mainnet, not {}", network),
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// Whether coins sent to `address` would arrive on `network`
+pub fn address_is_for(address: &Address, network: Network) -> bool {
+    match (address.network, network) {
+        (address_network, network) if address_network == network => true,
+        // Only bech32 has a separate regtest prefix, base58 addresses parse as testnet
+        (Network::Testnet, Network::Regtest) => match address.payload {
+            Payload::WitnessProgram { .. } => false,
+            _ => true,
+        },
+        _ => false,
+    }
+}
+
+/// SLIP-132 version bytes for extended public keys, mapped to the BIP32 version they stand in
+/// for and the script type they imply
+const PUBKEY_VERSIONS: [([u8; 4], [u8; 4], &str); 6] = [
+    ([0x04, 0x88, 0xB2, 0x1E], [0x04, 0x88, 0xB2, 0x1E], "pkh"), // xpub
+    (
+        [0x04, 0x9D, 0x7C, 0xB2],
+        [0x04, 0x88, 0xB2, 0x1E],
+        "sh(wpkh",
+    ), // ypub
+    ([0x04, 0xB2, 0x47, 0x46], [0x04, 0x88, 0xB2, 0x1E], "wpkh"), // zpub
+    ([0x04, 0x35, 0x87, 0xCF], [0x04, 0x35, 0x87, 0xCF], "pkh"), // tpub
+    (
+        [0x04, 0x4A, 0x52, 0x62],
+        [0x04, 0x35, 0x87, 0xCF],
+        "sh(wpkh",
+    ), // upub
+    ([0x04, 0x5F, 0x1C, 0xF6], [0x04, 0x35, 0x87, 0xCF], "wpkh"), // vpub
 ];
+
+/// Turn an xpub/ypub/zpub (or testnet equivalent) or a public output descriptor into a
+/// watch-only receive descriptor
+pub fn watch_only_descriptor(input: &str) -> Result<String, Error> {
+    let input = input.trim();
+    if input.contains('(') {
+        // Before comparing with the public form, which gets a fresh checksum
+        if input.contains('#') && with_checksum(input)? != input {
+            return Err(Error::Import(
+                "Descriptor checksum doesn't match".to_string(),
+            ));
+        }
+        if public_descriptor(input) != input {
+            return Err(Error::Import(
+                "Descriptor contains private keys".to_string(),
+            ));
+        }
+        if fingerprint(input).is_none() {
+            return Err(Error::Import(
+                "Descriptor has no extended public key".to_string(),
+            ));
+        }
+        return Ok(input.to_string());
+    }
+
+    let (xpub, script) = parse_xpub(input)?;
+    let closing = ")".repeat(script.matches('(').count() + 1);
+    Ok(format!("{}({}/0/*{}", script, xpub, closing))
+}
+
+/// Virtual size of `tx` once every input is signed, assuming they all spend from `descriptor`.
+/// Counts the unsigned transaction as built plus worst case signatures, so it may be a few vB
+/// over. None for script types we don't know the signed size of.
+pub fn estimated_vsize(tx: &Transaction, descriptor: &str) -> Option<u64> {
+    // Bytes signing adds to each input's scriptSig, which weigh four units each, and witness
+    const SIGNATURE: u64 = 1 + 72;
+    const PUBKEY: u64 = 1 + 33;
+    let (script_sig, witness) = if descriptor.starts_with("wpkh(") {
+        (0, 1 + SIGNATURE + PUBKEY)
+    } else if descriptor.starts_with("sh(wpkh(") {
+        // Pushes the 22 byte witness program
+        (1 + 22, 1 + SIGNATURE + PUBKEY)
+    } else if descriptor.starts_with("pkh(") {
+        (SIGNATURE + PUBKEY, 0)
+    } else if descriptor.starts_with("wsh(sortedmulti(") || descriptor.starts_with("wsh(multi(") {
+        let start = descriptor.find("multi(")? + "multi(".len();
+        let end = start + descriptor[start..].find(',')?;
+        let threshold: u64 = descriptor[start..end].parse().ok()?;
+        let keys = key_fingerprints(descriptor).len() as u64;
+        // OP_m, the keys, OP_n, OP_CHECKMULTISIG
+        let script = 1 + keys * PUBKEY + 1 + 1;
+        let script_length = if script < 253 { 1 } else { 3 };
+        // Item count, the empty item CHECKMULTISIG pops by mistake, the signatures, the script
+        (0, 1 + 1 + threshold * SIGNATURE + script_length + script)
+    } else {
+        return None;
+    };
+    let inputs = tx.input.len() as u64;
+    // Segwit marker and flag
+    let marker = if witness > 0 { 2 } else { 0 };
+    let weight = tx.get_weight() as u64 + inputs * (script_sig * 4 + witness) + marker;
+    Some((weight + 3) / 4)
+}
+
+/// Rows of a payments CSV as `(address, amount, label)`, unchecked. A header row starting with
+/// "address" is skipped, and the label may be missing or quoted.
+pub fn parse_recipients(csv: &str) -> Result<Vec<(String, String, String)>, Error> {
+    let unquote = |field: &str| field.trim().trim_matches('"').trim().to_string();
+    let mut rows = vec![];
+    for (i, line) in csv.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.splitn(3, ',').collect();
+        if rows.is_empty() && unquote(fields[0]).eq_ignore_ascii_case("address") {
+            continue;
+        }
+        if fields.len() < 2 {
+            return Err(Error::File(format!(
+                "Line {}: expected an address, an amount and an optional label",
+                i + 1
+            )));
+        }
+        rows.push((
+            unquote(fields[0]),
+            unquote(fields[1]),
+            fields
+                .get(2)
+                .map_or_else(String::new, |label| unquote(label)),
+        ));
+    }
+    Ok(rows)
+}
+
+/// Parse an xpub/ypub/zpub (or testnet equivalent) into a plain BIP32 key and the script type
+/// its version implies
+pub fn parse_xpub(input: &str) -> Result<(ExtendedPubKey, &'static str), Error> {
+    let mut data = base58::from_check(input.trim())
+        .map_err(|_| Error::Import("Not a valid extended public key".to_string()))?;
+    if data.len() < 4 {
+        return Err(Error::Import("Not a valid extended public key".to_string()));
+    }
+    let (bip32_version, script) = PUBKEY_VERSIONS
+        .iter()
+        .find(|(version, _, _)| data[..4] == version[..])
+        .map(|(_, bip32_version, script)| (bip32_version, script))
+        .ok_or_else(|| Error::Import("Unknown extended public key version".to_string()))?;
+    data[..4].copy_from_slice(bip32_version);
+    let xpub = ExtendedPubKey::from_str(&base58::check_encode_slice(&data))
+        .map_err(|_| Error::Import("Not a valid extended public key".to_string()))?;
+    Ok((xpub, script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::{FromHex, ToHex};
+
+    // From https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+    const VECTORS: [(&str, &str, &str); 4] = [
+        (
+            "00000000000000000000000000000000",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        ),
+        (
+            "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+        ),
+        (
+            "80808080808080808080808080808080",
+            "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+            "d71de856f81a8acc65e6fc851a38d4d7ec216fd0796d0a6827a3ad6ed5511a30fa280f12eb2e47ed2ac03b5c462a0358d18d69fe4f985ec81778c1b370b652a8",
+        ),
+        (
+            "ffffffffffffffffffffffffffffffff",
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+            "ac27495480225222079d7be181583751e86f571027b0497b5b5d11218e0a8a13332572917f0f8e5a589620c6f15b11c61dee327651a14c34e18231052e48c069",
+        ),
+    ];
+
+    #[test]
+    fn bip39_vectors() {
+        for (entropy, sentence, expected_seed) in VECTORS.iter() {
+            let words =
+                mnemonic(&Vec::<u8>::from_hex(entropy).unwrap(), Language::English).unwrap();
+            assert_eq!(words.join(" "), *sentence);
+            assert_eq!(seed(&words, "TREZOR").to_hex(), *expected_seed);
+        }
+    }
+
+    #[test]
+    fn entropy_roundtrip() {
+        for (entropy_hex, sentence, _) in VECTORS.iter() {
+            let words: Vec<String> = sentence.split(' ').map(String::from).collect();
+            assert_eq!(
+                entropy(&words, Language::English).unwrap().to_hex(),
+                *entropy_hex
+            );
+        }
+    }
+
+    #[test]
+    fn japanese_vector() {
+        // First vector from https://github.com/bip32JP/bip32JP.github.io/blob/master/test_JP_BIP39.json
+        let words = mnemonic(&[0u8; 16], Language::Japanese).unwrap();
+        assert_eq!(words[0], "あいこくしん");
+        assert_eq!(words[11], "あおぞら");
+        assert_eq!(
+            seed(&words, "㍍ガバヴァぱばぐゞちぢ十人十色").to_hex(),
+            "a262d6fb6122ecf45be09c50492b31f92e9beb7d9a845987a02cefda57a15f9c467a17872029a9e92299b5cbdf306e3a0ee620245cbd508959b6cb7ca637bd55"
+        );
+        assert_eq!(entropy(&words, Language::Japanese).unwrap(), vec![0u8; 16]);
+    }
+
+    #[test]
+    fn every_wordlist_is_complete() {
+        for &language in Language::all().iter() {
+            let list = wordlist(language);
+            assert_eq!(list.len(), 2048, "{:?}", language);
+            // Decoding needs every word to be unique
+            let mut normalized: Vec<String> = list.iter().map(|w| normalize(w)).collect();
+            normalized.sort();
+            normalized.dedup();
+            assert_eq!(normalized.len(), 2048, "{:?}", language);
+        }
+    }
+
+    #[test]
+    fn accents_match_however_typed() {
+        // "ábaco" composed, while the list stores it decomposed
+        assert_eq!(suggestions("\u{e1}bac", 1, Language::Spanish).len(), 1);
+    }
+
+    #[test]
+    fn entropy_rejects_bad_mnemonics() {
+        let mut words = mnemonic(&[0u8; 16], Language::English).unwrap();
+        words.swap(0, 11);
+        assert!(matches!(
+            entropy(&words, Language::English),
+            Err(Error::InvalidChecksum)
+        ));
+
+        words[4] = "notaword".to_string();
+        assert!(matches!(
+            entropy(&words, Language::English),
+            Err(Error::InvalidWord(4))
+        ));
+
+        words.truncate(11);
+        assert!(matches!(
+            entropy(&words, Language::English),
+            Err(Error::Mnemonic(_))
+        ));
+    }
+
+    #[test]
+    fn watch_only_from_zpub() {
+        // BIP84 account 0 of "abandon ... about"
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        assert_eq!(
+            watch_only_descriptor(zpub).unwrap(),
+            "wpkh(xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)"
+        );
+    }
+
+    #[test]
+    fn watch_only_descriptor_checksum() {
+        let descriptor = "wpkh(xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)";
+        let checked = with_checksum(descriptor).unwrap();
+        assert_eq!(watch_only_descriptor(&checked).unwrap(), checked);
+
+        let mistyped = format!("{}#aaaaaaaa", descriptor);
+        match watch_only_descriptor(&mistyped) {
+            Err(Error::Import(message)) => assert_eq!(message, "Descriptor checksum doesn't match"),
+            other => panic!("expected a checksum error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn public_descriptor_checksum() {
+        let secp = Secp256k1::new();
+        let xprv = ExtendedPrivKey::new_master(Network::Testnet, &[1u8; 32]).unwrap();
+        let xpub = ExtendedPubKey::from_private(&secp, &xprv);
+        let private = format!("wpkh({}/0/*)", xprv);
+        let public = format!("wpkh({}/0/*)", xpub);
+        assert_eq!(public_descriptor(&private), public);
+        assert_eq!(
+            public_descriptor(&with_checksum(&private).unwrap()),
+            with_checksum(&public).unwrap()
+        );
+    }
+
+    #[test]
+    fn change_from_receive_descriptor() {
+        assert_eq!(
+            change_descriptor("wsh(sortedmulti(1,xpubA/0/*,xpubB/0/*))#abcdefgh").unwrap(),
+            "wsh(sortedmulti(1,xpubA/1/*,xpubB/1/*))"
+        );
+        assert!(change_descriptor("wpkh(xpubA/*)").is_none());
+    }
+
+    #[test]
+    fn descriptor_checksum() {
+        // From BIP380
+        assert_eq!(
+            with_checksum("raw(deadbeef)").unwrap(),
+            "raw(deadbeef)#89f8spxm"
+        );
+        assert_eq!(
+            with_checksum("raw(deadbeef)#aaaaaaaa").unwrap(),
+            "raw(deadbeef)#89f8spxm"
+        );
+        assert!(with_checksum("raw(deadbeef)\u{e9}").is_err());
+    }
+
+    #[test]
+    fn bip84_account_key() {
+        // BIP84 test vector: account 0 of "abandon ... about"
+        let words = mnemonic(&[0u8; 16], Language::English).unwrap();
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &seed(&words, "")).unwrap();
+        let secp = Secp256k1::new();
+        let path = account_path(84, Network::Bitcoin, 0);
+        let xprv = master.derive_priv(&secp, &path).unwrap();
+        let descriptor = format!(
+            "wpkh({}{}/0/*)",
+            key_origin(master.fingerprint(&secp), &path),
+            xprv
+        );
+        assert_eq!(
+            public_descriptor(&descriptor),
+            "wpkh([73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V/0/*)"
+        );
+        assert_eq!(fingerprint(&descriptor).unwrap().to_string(), "73c5da0a");
+        assert_eq!(
+            parse_key_origin(&descriptor),
+            Some((master.fingerprint(&secp), path))
+        );
+    }
+
+    #[test]
+    fn key_origin_on_import() {
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let (origin, key) = split_key_origin(&format!(" [73c5da0a/84'/0'/0']{} ", xpub)).unwrap();
+        let (fingerprint, path) = origin.unwrap();
+        assert_eq!(fingerprint.to_string(), "73c5da0a");
+        assert_eq!(path, account_path(84, Network::Bitcoin, 0));
+        assert_eq!(key, xpub);
+
+        assert_eq!(split_key_origin(xpub).unwrap(), (None, xpub));
+        assert!(split_key_origin(&format!("[73c5da0a/84'{}", xpub)).is_err());
+        assert!(split_key_origin(&format!("[nothex]{}", xpub)).is_err());
+    }
+
+    #[test]
+    fn key_network_must_match() {
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let descriptor = watch_only_descriptor(zpub).unwrap();
+        assert!(check_network(&descriptor, Network::Bitcoin).is_ok());
+        assert!(check_network(&descriptor, Network::Testnet).is_err());
+        assert!(check_network(&descriptor, Network::Regtest).is_err());
+    }
+
+    #[test]
+    fn address_network() {
+        let bech32 = Address::from_str("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap();
+        assert!(address_is_for(&bech32, Network::Testnet));
+        assert!(!address_is_for(&bech32, Network::Regtest));
+        assert!(!address_is_for(&bech32, Network::Bitcoin));
+
+        let base58 = Address::from_str("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn").unwrap();
+        assert!(address_is_for(&base58, Network::Testnet));
+        assert!(address_is_for(&base58, Network::Regtest));
+        assert!(!address_is_for(&base58, Network::Bitcoin));
+    }
+
+    #[test]
+    fn dice_entropy() {
+        let dice = rolls("1234 5612, 3456", EntropySource::Dice).unwrap();
+        assert_eq!(dice, "123456123456");
+        assert_eq!(roll_bits(&dice, EntropySource::Dice), 31);
+        assert_eq!(
+            entropy_from_rolls(&dice, Entropy::Low, false).to_hex(),
+            "958d51602bbfbd18b2a084ba848a827c"
+        );
+        assert_ne!(
+            entropy_from_rolls(&dice, Entropy::Low, true),
+            entropy_from_rolls(&dice, Entropy::Low, false)
+        );
+        assert!(matches!(
+            rolls("1237", EntropySource::Dice),
+            Err(Error::InvalidRoll(3))
+        ));
+        assert!(matches!(
+            rolls("12, 3 7", EntropySource::Dice),
+            Err(Error::InvalidRoll(6))
+        ));
+        assert_eq!(rolls("h t H", EntropySource::Coins).unwrap(), "HTH");
+    }
+
+    #[test]
+    fn bip39_master_key() {
+        let words = mnemonic(&[0u8; 16], Language::English).unwrap();
+        let xprv = ExtendedPrivKey::new_master(Network::Bitcoin, &seed(&words, "TREZOR")).unwrap();
+        assert_eq!(
+            xprv.to_string(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+    }
+
+    #[test]
+    fn signed_size_estimate() {
+        use bitcoin::blockdata::script::Script;
+        use bitcoin::blockdata::transaction::{OutPoint, TxIn, TxOut};
+
+        // One input, two P2WPKH outputs
+        let output = TxOut {
+            value: 1000,
+            script_pubkey: Script::from(vec![0u8; 22]),
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: 0xffff_ffff,
+                witness: vec![],
+            }],
+            output: vec![output.clone(), output],
+        };
+        assert_eq!(tx.get_weight(), 452);
+        assert_eq!(estimated_vsize(&tx, "wpkh(xpubA/0/*)"), Some(141));
+        assert_eq!(estimated_vsize(&tx, "sh(wpkh(xpubA/0/*))"), Some(164));
+        assert_eq!(estimated_vsize(&tx, "pkh(xpubA/0/*)"), Some(220));
+        let multisig =
+            "wsh(sortedmulti(2,[00000001]xpubA/0/*,[00000002]xpubB/0/*,[00000003]xpubC/0/*))";
+        assert_eq!(estimated_vsize(&tx, multisig), Some(177));
+        assert_eq!(estimated_vsize(&tx, "tr(xpubA/0/*)"), None);
+    }
+
+    #[test]
+    fn recipients_csv() {
+        let csv = "address,amount,label\n\
+                   bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq, 1000, Alice\n\
+                   \n\
+                   1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2,2500\n\
+                   3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy,300,\"Bob, Inc\"\n";
+        let rows = parse_recipients(csv).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            (
+                "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+                "1000".to_string(),
+                "Alice".to_string()
+            )
+        );
+        assert_eq!(rows[1].2, "");
+        assert_eq!(rows[2].2, "Bob, Inc");
+        assert!(parse_recipients("address,amount\njust-an-address\n").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bitcoin::Network;
+use magical_bitcoin_wallet::blockchain::ElectrumBlockchain;
+use magical_bitcoin_wallet::sled;
+use magical_bitcoin_wallet::Client as ElectrumClient;
+use magical_bitcoin_wallet::{OfflineWallet, Wallet};
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+
+use crate::config;
+use crate::data::Account;
+use crate::electrum;
+use crate::error::{consume_library_error, Error};
+
+pub type ElectrumWallet = Wallet<ElectrumBlockchain, sled::Tree>;
+
+/// Shared by every `Command::perform` future
+pub static WALLETS: Lazy<WalletManager> = Lazy::new(WalletManager::new);
+
+struct Cached {
+    // Rebuild the wallet if the account's descriptors change under us
+    descriptor: String,
+    change_descriptor: Option<String>,
+    wallet: Arc<Mutex<ElectrumWallet>>,
+}
+
+impl Cached {
+    fn is_for(&self, account: &Account) -> bool {
+        self.descriptor == account.descriptor && self.change_descriptor == account.change_descriptor
+    }
+}
+
+/// Keeps one open sled database per network, one connected wallet per account and one client
+/// per network for calls outside any wallet, like fee estimates. Connections open on first use
+/// and are dropped after a server error, so the next call reconnects.
+pub struct WalletManager {
+    databases: Mutex<HashMap<Network, sled::Db>>,
+    wallets: Mutex<HashMap<Uuid, Cached>>,
+    clients: Mutex<HashMap<Network, Arc<Mutex<ElectrumClient>>>>,
+}
+
+impl WalletManager {
+    fn new() -> Self {
+        Self {
+            databases: Mutex::new(HashMap::new()),
+            wallets: Mutex::new(HashMap::new()),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+    /// Sled locks its directory, so each network's database must only be opened once
+    pub fn database(&self, network: Network) -> Result<sled::Db, Error> {
+        let mut databases = self.databases.lock().unwrap();
+        if let Some(db) = databases.get(&network) {
+            return Ok(db.clone());
+        }
+        let db = sled::open(config::get().network_dir(network))?;
+        databases.insert(network, db.clone());
+        Ok(db)
+    }
+    fn connect(&self, account: &Account) -> Result<ElectrumWallet, Error> {
+        let client = electrum::connect(config::get().servers.get(account.network))?;
+        let blockchain = ElectrumBlockchain::from(client);
+        let tree = self
+            .database(account.network)?
+            .open_tree(account.tree_name())?;
+
+        Wallet::new(
+            &account.descriptor,
+            account.change_descriptor.as_deref(),
+            // HELP: self network or node network?
+            account.network,
+            tree,
+            blockchain,
+        )
+        .map_err(Error::from)
+    }
+    fn cached(&self, account: &Account) -> Option<Arc<Mutex<ElectrumWallet>>> {
+        let wallets = self.wallets.lock().unwrap();
+        wallets
+            .get(&account.id)
+            .filter(|cached| cached.is_for(account))
+            .map(|cached| cached.wallet.clone())
+    }
+    fn get(&self, account: &Account) -> Result<Arc<Mutex<ElectrumWallet>>, Error> {
+        if let Some(wallet) = self.cached(account) {
+            return Ok(wallet);
+        }
+        // Connect without the lock, so a slow server or proxy doesn't hold up other accounts
+        let wallet = Arc::new(Mutex::new(self.connect(account)?));
+        let mut wallets = self.wallets.lock().unwrap();
+        // Someone else may have connected meanwhile, in which case keep theirs
+        if let Some(cached) = wallets
+            .get(&account.id)
+            .filter(|cached| cached.is_for(account))
+        {
+            return Ok(cached.wallet.clone());
+        }
+        wallets.insert(
+            account.id,
+            Cached {
+                descriptor: account.descriptor.clone(),
+                change_descriptor: account.change_descriptor.clone(),
+                wallet: wallet.clone(),
+            },
+        );
+        Ok(wallet)
+    }
+    /// Run `f` against the account's wallet, connecting if needed
+    pub fn with_wallet<T>(
+        &self,
+        account: &Account,
+        f: impl FnOnce(&ElectrumWallet) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let wallet = self.get(account)?;
+        let result = f(&wallet.lock().unwrap());
+        if let Err(Error::Server(_)) = result {
+            // The connection may be dead, reconnect next time
+            self.wallets.lock().unwrap().remove(&account.id);
+        }
+        result
+    }
+    fn client(&self, network: Network) -> Result<Arc<Mutex<ElectrumClient>>, Error> {
+        if let Some(client) = self.clients.lock().unwrap().get(&network) {
+            return Ok(client.clone());
+        }
+        // Connect without the lock, like wallets
+        let client = Arc::new(Mutex::new(electrum::connect(
+            config::get().servers.get(network),
+        )?));
+        let mut clients = self.clients.lock().unwrap();
+        Ok(clients.entry(network).or_insert(client).clone())
+    }
+    /// Run `f` against the network's server, connecting if needed
+    pub fn with_client<T>(
+        &self,
+        network: Network,
+        f: impl FnOnce(&ElectrumClient) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let client = self.client(network)?;
+        let result = f(&client.lock().unwrap());
+        if let Err(Error::Server(_)) = result {
+            self.clients.lock().unwrap().remove(&network);
+        }
+        result
+    }
+    /// Drop every cached wallet and client so the next call reconnects with the current
+    /// settings
+    pub fn disconnect_all(&self) {
+        self.wallets.lock().unwrap().clear();
+        self.clients.lock().unwrap().clear();
+    }
+    /// Offline wallet over the account's tree for signing. Never cached, since it holds
+    /// private keys.
+    pub fn signing_wallet(
+        &self,
+        account: &Account,
+        descriptor: &str,
+        change_descriptor: Option<&str>,
+    ) -> Result<OfflineWallet<sled::Tree>, Error> {
+        let tree = self
+            .database(account.network)?
+            .open_tree(account.tree_name())?;
+        Wallet::new_offline(descriptor, change_descriptor, account.network, tree)
+            .map_err(consume_library_error)
+    }
+}